@@ -1,37 +1,179 @@
 use bevy::prelude::*;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::config::Params;
 use crate::field::Signals;
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Component)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Component, Serialize, Deserialize)]
 #[repr(u8)]
 pub enum SignalKind {
     Exploring,
     Retrieving,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Component, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum Caste {
+    #[default]
+    Worker,
+    Scout,
+    Soldier,
+}
+
+// How a caste differs from a worker: multipliers on speed, carrying capacity
+// and trail following, plus how far ahead it samples the pheromone fields.
+pub struct CasteProfile {
+    pub speed: f32,
+    pub capacity: f32,
+    pub trail_response: f32,
+    pub wander: f32,
+    pub sensing_range: f32,
+    // Negative for castes that run towards alarm rather than away from it.
+    pub alarm_response: f32,
+}
+
+impl Caste {
+    pub const ALL: [Caste; 3] = [Caste::Worker, Caste::Scout, Caste::Soldier];
+
+    pub fn profile(&self) -> CasteProfile {
+        match self {
+            Caste::Worker => CasteProfile {
+                speed: 1.0,
+                capacity: 1.0,
+                trail_response: 1.0,
+                wander: 1.0,
+                sensing_range: 0.0,
+                alarm_response: 1.0,
+            },
+            // Fast, light and mostly deaf to trails, so they keep finding new food.
+            Caste::Scout => CasteProfile {
+                speed: 1.3,
+                capacity: 0.5,
+                trail_response: 0.25,
+                wander: 1.5,
+                sensing_range: 30.0,
+                alarm_response: 1.5,
+            },
+            Caste::Soldier => CasteProfile {
+                speed: 0.8,
+                capacity: 0.5,
+                trail_response: 1.0,
+                wander: 0.75,
+                sensing_range: 10.0,
+                alarm_response: -1.0,
+            },
+        }
+    }
+
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Caste::Scout,
+            2 => Caste::Soldier,
+            _ => Caste::Worker,
+        }
+    }
+}
+
+// What an ant sensed on its last update and how each steering term pulled on
+// it. Only kept for ants someone is looking at.
+#[derive(Copy, Clone, Debug, Default, Component)]
+pub struct Perception {
+    pub sensor: Vec2,
+    pub exploring: Vec2,
+    pub retrieving: Vec2,
+    pub alarm: Vec2,
+    pub trail_direction: Vec2,
+    pub alarm_direction: Vec2,
+    // Weight, gas and steering of each term in TERMS.
+    pub terms: [(f32, f32, f32); 3],
+    pub gas: f32,
+    pub steering: f32,
+}
+
+impl Perception {
+    pub const TERMS: [&'static str; 3] = ["wander", "trail", "alarm"];
+}
+
 #[derive(Debug, Component)]
 pub struct Ant {
     pub state: SignalKind,
     pub position: Vec2,
     pub velocity: Vec2,
+    pub speed: f32,
+    pub age: f32,
 }
 
-impl Ant {
-    const MAX_SPEED: f32 = 200f32;
+// Per-ant copies of the behaviour constants in `Params`, so that individuals
+// can differ and pass their differences on to offspring.
+#[derive(Copy, Clone, Debug, PartialEq, Component, Serialize, Deserialize)]
+pub struct Traits {
+    pub max_speed: f32,
+    pub wander_amplitude: f32,
+    pub wander_weight: f32,
+    pub trail_weight: f32,
+    #[serde(default)]
+    pub sensing_range: f32,
+    #[serde(default)]
+    pub alarm_weight: f32,
+}
 
+impl Traits {
+    pub fn from_params(params: &Params) -> Self {
+        Self {
+            max_speed: params.max_speed,
+            wander_amplitude: params.wander_amplitude,
+            wander_weight: params.wander_weight,
+            trail_weight: params.trail_weight,
+            sensing_range: 0.0,
+            alarm_weight: params.alarm_weight,
+        }
+    }
+
+    // The traits an ant actually behaves with once its caste is applied.
+    pub fn for_caste(&self, caste: Caste) -> Self {
+        let profile = caste.profile();
+        Self {
+            max_speed: self.max_speed * profile.speed,
+            wander_amplitude: self.wander_amplitude * profile.wander,
+            wander_weight: self.wander_weight,
+            trail_weight: self.trail_weight * profile.trail_response,
+            sensing_range: self.sensing_range + profile.sensing_range,
+            alarm_weight: self.alarm_weight * profile.alarm_response,
+        }
+    }
+
+    // Scales every trait by its own random factor in [1 - spread, 1 + spread].
+    pub fn vary(&self, spread: f32, rng: &mut impl Rng) -> Self {
+        if spread <= 0.0 {
+            return *self;
+        }
+        let mut factor = || 1.0 + rng.gen_range(-spread..=spread);
+        Self {
+            max_speed: (self.max_speed * factor()).max(0.0),
+            wander_amplitude: (self.wander_amplitude * factor()).max(0.0),
+            wander_weight: (self.wander_weight * factor()).max(0.0),
+            trail_weight: (self.trail_weight * factor()).max(0.0),
+            sensing_range: self.sensing_range,
+            alarm_weight: (self.alarm_weight * factor()).max(0.0),
+        }
+    }
+}
+
+impl Ant {
     pub fn new() -> Self {
         Self {
             state: SignalKind::Exploring,
             position: Vec2::ZERO,
             velocity: Vec2::X,
+            speed: 0.0,
+            age: 0.0,
         }
     }
 
-    fn random_walk(&self) -> (f32, f32) {
-        let mut rng = rand::thread_rng();
+    fn random_walk(&self, amplitude: f32, rng: &mut impl Rng) -> (f32, f32) {
         let steer: f32 = rng.gen::<f32>() * 180.0f32.to_radians() - 90.0f32.to_radians();
-        return (1.0, 2.0*steer);
+        return (1.0, amplitude*steer);
     }
 
     fn follow(&self, direction: Vec2) -> (f32, f32) {
@@ -54,22 +196,25 @@ impl Ant {
         return (gas, steering);
     }
 
-    pub fn percieve_signals(&self, signals: &Signals) -> (f32, f32) {
-        let mut weights = [20.0, 0.0];
-        let (g, s) = self.random_walk();
+    pub fn percieve_signals(&self, signals: &Signals, traits: &Traits, rng: &mut impl Rng) -> Perception {
+        let mut weights = [traits.wander_weight, 0.0, 0.0];
+        let (g, s) = self.random_walk(traits.wander_amplitude, rng);
 
-        let mut gasses = [g, 0.0];
-        let mut steers = [s, 0.0];
+        let mut gasses = [g, 0.0, 0.0];
+        let mut steers = [s, 0.0, 0.0];
         
-        let exploring_sig = signals.get_field(SignalKind::Exploring).sample(self.position);
-        let retrieving_sig = signals.get_field(SignalKind::Retrieving).sample(self.position);
+        let sensor = self.position + traits.sensing_range * self.velocity.normalize_or_zero();
+        let exploring_sig = signals.get_field(SignalKind::Exploring).sample(sensor);
+        let retrieving_sig = signals.get_field(SignalKind::Retrieving).sample(sensor);
+        let alarm_sig = signals.alarm.sample(sensor);
 
-        match self.state {
+        let trail_direction = match self.state {
             SignalKind::Exploring => {
-                weights[1] += 20.;
+                weights[1] += traits.trail_weight;
                 let (g, s) = self.follow(-retrieving_sig);
                 gasses[1] += g;
                 steers[1] += s;
+                -retrieving_sig
             },
             SignalKind::Retrieving => {
                 let desired_direction = retrieving_sig + (-exploring_sig);
@@ -77,25 +222,51 @@ impl Ant {
                 let (g, s) = self.follow(desired_direction);
                 gasses[1] += g;
                 steers[1] += s;
+                desired_direction
             },
-        }
+        };
+
+        // alarm drowns out everything else once it is strong enough
+        let alarm = alarm_sig.length();
+        let alarm_direction = traits.alarm_weight.signum() * alarm_sig;
+        weights[2] = traits.alarm_weight.abs() * alarm / (alarm + 1.0);
+        let (g, s) = self.follow(alarm_direction);
+        gasses[2] = g;
+        steers[2] = s;
 
         let normalize: f32 = f32::powi(weights.iter().sum(), -1);
         let gas: f32 = normalize * weights.iter().zip(gasses).map(|(&w, g): (&f32, f32)| w * g).sum::<f32>();
         let steering: f32 = normalize * weights.iter().zip(steers).map(|(&w, s)| w * s).sum::<f32>();
         
-        return (gas, steering);
+        Perception {
+            sensor,
+            exploring: exploring_sig,
+            retrieving: retrieving_sig,
+            alarm: alarm_sig,
+            trail_direction,
+            alarm_direction,
+            terms: [0, 1, 2].map(|i| (weights[i], gasses[i], steers[i])),
+            gas,
+            steering,
+        }
     }
 
 
-    pub fn leave_signal(&self, sigs: &mut Signals) {
-        let sig = 10.0 * self.velocity.clone().normalize();
+    pub fn leave_signal(&self, sigs: &mut Signals, strength: f32) {
+        let sig = strength * self.velocity.clone().normalize();
         sigs.get_mut_field(self.state)
             .acc_cell_value(sig, self.position);
     }
 
-    pub fn update(&mut self, sigs: &Signals, &dt: &f32) {
-        let (mut gas, mut steer) = self.percieve_signals(sigs);
+    // Marks the spot with a warning that points away from the danger.
+    pub fn raise_alarm(&self, sigs: &mut Signals, danger: Vec2, strength: f32) {
+        let sig = strength * (self.position - danger).normalize_or_zero();
+        sigs.alarm.acc_cell_value(sig, self.position);
+    }
+
+    pub fn update(&mut self, sigs: &Signals, params: &Params, traits: &Traits, rng: &mut impl Rng, &dt: &f32) -> Perception {
+        let perception = self.percieve_signals(sigs, traits, rng);
+        let (mut gas, mut steer) = (perception.gas, perception.steering);
         if gas.is_nan() {
             gas = 1.0;
         }
@@ -104,9 +275,12 @@ impl Ant {
         }
         steer = steer.clamp(-180f32.to_radians(), 180.0f32.to_radians());
         
-        let new_heading = (self.velocity + steer * 5.0 * dt * self.velocity.perp()).normalize();
+        let new_heading = (self.velocity + steer * params.steering_gain * dt * self.velocity.perp()).normalize();
         self.velocity = new_heading;
-        self.position += Self::MAX_SPEED * gas * dt * self.velocity;
+        self.speed = traits.max_speed * gas;
+        self.age += dt;
+        self.position += self.speed * dt * self.velocity;
+        perception
     }
 
     pub fn reach_around(&mut self, rect: Rect) {
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::tasks::block_on;
+use serde::{Deserialize, Serialize};
+
+use crate::ant::SignalKind;
+use crate::replay::AntFrame;
+use crate::EntityFactories;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AntRendering {
+    // One sprite per ant. Simple, and the only choice before the batch existed.
+    #[default]
+    Sprites,
+    // Every ant as a triangle in one shared mesh, rebuilt each frame.
+    Batched,
+}
+
+// Asks for an adapter the way the renderer would, so a machine without a GPU
+// can be told apart before the renderer gives up on it.
+pub fn gpu_available() -> bool {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::all()),
+        ..default()
+    });
+    block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).is_some()
+}
+
+pub fn state_color(state: SignalKind) -> Color {
+    match state {
+        SignalKind::Exploring => Color::RED,
+        SignalKind::Retrieving => Color::GREEN,
+    }
+}
+
+// A triangle pointing along the heading, as long and wide as the ant's sprite.
+pub fn triangle(ant: &AntFrame) -> [Vec2; 3] {
+    let size = EntityFactories::ant_size(ant.caste);
+    let forward = Vec2::from_angle(ant.heading);
+    let side = forward.perp();
+    [
+        ant.position + 0.5 * size.x * forward,
+        ant.position - 0.5 * size.x * forward + 0.5 * size.y * side,
+        ant.position - 0.5 * size.x * forward - 0.5 * size.y * side,
+    ]
+}
+
+pub fn empty_mesh() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    write_mesh(&mut mesh, &[]);
+    mesh
+}
+
+pub fn write_mesh(mesh: &mut Mesh, ants: &[AntFrame]) {
+    let mut positions = Vec::with_capacity(3 * ants.len());
+    let mut colors = Vec::with_capacity(3 * ants.len());
+    for ant in ants {
+        let color = state_color(ant.state).as_linear_rgba_f32();
+        for corner in triangle(ant) {
+            positions.push(corner.extend(0.0).to_array());
+            colors.push(color);
+        }
+    }
+    let indices = (0..positions.len() as u32).collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+}
@@ -0,0 +1,175 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::Config;
+
+#[derive(Parser, Debug)]
+#[command(name = "ants", about = "Ant colony foraging simulation")]
+pub struct Cli {
+    /// RON scenario file; the flags below override it
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Seed for the simulation RNG
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Number of ants to spawn
+    #[arg(long, global = true)]
+    pub ants: Option<usize>,
+
+    /// PNG map to load food, walls and nests from
+    #[arg(long, global = true)]
+    pub map: Option<PathBuf>,
+
+    /// Window size, e.g. 1280x720
+    #[arg(long, global = true, value_parser = parse_window)]
+    pub window: Option<(f32, f32)>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the simulation in a window
+    Run(Outputs),
+    /// Run a fixed number of ticks without a window
+    Headless {
+        #[arg(long)]
+        ticks: u64,
+        /// Metrics file, CSV or NDJSON by extension
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Trajectory recording to write
+        #[arg(long)]
+        record: Option<PathBuf>,
+        #[command(flatten)]
+        images: Images,
+        /// Snapshot to start from instead of a fresh colony
+        #[arg(long)]
+        resume: Option<PathBuf>,
+        /// Snapshot written once the run ends
+        #[arg(long)]
+        save_snapshot: Option<PathBuf>,
+    },
+    /// Play back a trajectory recording
+    Replay { file: PathBuf },
+    /// Time a headless run and report ticks per second
+    Bench {
+        #[arg(long, default_value_t = 1000)]
+        ticks: u64,
+    },
+    /// Run headless over a grid or random sample of parameters
+    Sweep {
+        /// RON sweep spec with `grid`, `random` and `samples`
+        spec: PathBuf,
+        #[arg(long)]
+        ticks: u64,
+        /// Runs per parameter set, seeded from --seed upwards
+        #[arg(long, default_value_t = 1)]
+        seeds: u64,
+        /// Worker threads, defaults to one per core
+        #[arg(long)]
+        threads: Option<usize>,
+        /// Summary CSV with one row per run
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Search for behaviour parameters that deliver the most food
+    Evolve {
+        #[arg(long)]
+        ticks: u64,
+        #[arg(long, default_value_t = 20)]
+        population: usize,
+        #[arg(long, default_value_t = 10)]
+        generations: usize,
+        /// Runs per individual and generation
+        #[arg(long, default_value_t = 2)]
+        seeds: u64,
+        /// Worker threads, defaults to one per core
+        #[arg(long)]
+        threads: Option<usize>,
+        /// CSV log with the best individual of each generation
+        #[arg(long)]
+        out: PathBuf,
+    },
+}
+
+#[derive(Args, Debug, Default)]
+pub struct Outputs {
+    /// Trajectory recording to write
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+    /// Metrics file, CSV or NDJSON by extension
+    #[arg(long)]
+    pub metrics: Option<PathBuf>,
+    #[command(flatten)]
+    pub images: Images,
+    /// Snapshot to start from instead of a fresh colony
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+    /// Snapshot written once a headless run ends, or on F5 in a window
+    #[arg(long)]
+    pub save_snapshot: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct Images {
+    /// Directory to write a numbered PNG sequence into
+    #[arg(long)]
+    pub frames: Option<PathBuf>,
+    /// Keep only every Nth frame of the sequence
+    #[arg(long, default_value_t = 1)]
+    pub every: u64,
+    /// PNG written on F12, or once a headless run ends
+    #[arg(long)]
+    pub screenshot: Option<PathBuf>,
+    /// Draw the pheromone fields into headless images
+    #[arg(long)]
+    pub pheromones: bool,
+}
+
+impl Default for Images {
+    fn default() -> Self {
+        Self {
+            frames: None,
+            every: 1,
+            screenshot: None,
+            pheromones: false,
+        }
+    }
+}
+
+impl Cli {
+    pub fn config(&self) -> Config {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)
+                .unwrap_or_else(|err| panic!("Could not load config from {path:?}: {err}")),
+            None => Config::default(),
+        };
+        if self.seed.is_some() {
+            config.seed = self.seed;
+        }
+        if let Some(ants) = self.ants {
+            config.ants = ants;
+        }
+        if self.map.is_some() {
+            config.map = self.map.clone();
+        }
+        if let Some(window) = self.window {
+            config.window = window;
+        }
+        config
+    }
+}
+
+fn parse_window(arg: &str) -> Result<(f32, f32), String> {
+    let Some((width, height)) = arg.split_once('x') else {
+        return Err(format!("Expected WIDTHxHEIGHT, got {arg}"));
+    };
+    let width: f32 = width.parse().map_err(|_| format!("Bad window width {width}"))?;
+    let height: f32 = height.parse().map_err(|_| format!("Bad window height {height}"))?;
+    Ok((width, height))
+}
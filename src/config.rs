@@ -0,0 +1,210 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ant::Caste;
+use crate::batch::AntRendering;
+use crate::hazard::{Hazard, Predators};
+use crate::trail::Trails;
+
+// Scenario settings that are read once at startup. Anything left out of a
+// config file keeps its default.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub seed: Option<u64>,
+    pub ants: usize,
+    pub map: Option<PathBuf>,
+    pub window: (f32, f32),
+    pub params: Params,
+    // Relative spread of each ant's traits around `params` at spawn.
+    pub trait_spread: f32,
+    // Relative spread applied again when traits pass to offspring.
+    pub mutation: f32,
+    // Food a nest needs to raise one new ant; no births when unset.
+    pub birth_cost: Option<f32>,
+    // Seconds an ant lives; immortal when unset.
+    pub lifespan: Option<f32>,
+    pub castes: CasteRatios,
+    pub hazards: Vec<Hazard>,
+    pub predators: Predators,
+    pub ant_rendering: AntRendering,
+    pub trails: Trails,
+    // Debug gizmos are drawn for one ant in this many.
+    pub debug_every: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            ants: 1000,
+            map: None,
+            window: (1920.0, 1080.0),
+            params: Params::default(),
+            trait_spread: 0.0,
+            mutation: 0.05,
+            birth_cost: None,
+            lifespan: None,
+            castes: CasteRatios::default(),
+            hazards: vec![],
+            predators: Predators::default(),
+            ant_rendering: AntRendering::default(),
+            trails: Trails::default(),
+            debug_every: 20,
+        }
+    }
+}
+
+// Relative share of each caste among spawned ants. They needn't sum to one.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct CasteRatios {
+    pub worker: f32,
+    pub scout: f32,
+    pub soldier: f32,
+}
+
+impl Default for CasteRatios {
+    fn default() -> Self {
+        Self {
+            worker: 1.0,
+            scout: 0.0,
+            soldier: 0.0,
+        }
+    }
+}
+
+impl CasteRatios {
+    // Maps `u` in [0, 1) onto a caste in proportion to the ratios.
+    pub fn pick(&self, u: f32) -> Caste {
+        let shares = [self.worker, self.scout, self.soldier].map(|share| share.max(0.0));
+        let total: f32 = shares.iter().sum();
+        if total <= 0.0 {
+            return Caste::Worker;
+        }
+        let mut threshold = u * total;
+        for (caste, share) in Caste::ALL.into_iter().zip(shares) {
+            if threshold < share {
+                return caste;
+            }
+            threshold -= share;
+        }
+        // rounding can push `u` just past the end
+        Caste::ALL
+            .into_iter()
+            .zip(shares)
+            .filter(|&(_, share)| share > 0.0)
+            .last()
+            .map_or(Caste::Worker, |(caste, _)| caste)
+    }
+}
+
+// The behaviour constants that sweeps and optimisers are allowed to vary.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Params {
+    pub diffusion: f32,
+    pub evaporation: f32,
+    pub wander_weight: f32,
+    pub trail_weight: f32,
+    pub max_speed: f32,
+    pub capacity: f32,
+    pub steering_gain: f32,
+    pub wander_amplitude: f32,
+    pub deposit: f32,
+    pub alarm_weight: f32,
+    pub alarm_deposit: f32,
+    pub alarm_evaporation: f32,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self {
+            diffusion: 0.01,
+            evaporation: 0.03,
+            wander_weight: 20.0,
+            trail_weight: 20.0,
+            max_speed: 200.0,
+            capacity: 2.0,
+            steering_gain: 5.0,
+            wander_amplitude: 2.0,
+            deposit: 10.0,
+            alarm_weight: 40.0,
+            alarm_deposit: 30.0,
+            alarm_evaporation: 0.3,
+        }
+    }
+}
+
+impl Params {
+    pub const NAMES: [&'static str; 12] = [
+        "diffusion",
+        "evaporation",
+        "wander_weight",
+        "trail_weight",
+        "max_speed",
+        "capacity",
+        "steering_gain",
+        "wander_amplitude",
+        "deposit",
+        "alarm_weight",
+        "alarm_deposit",
+        "alarm_evaporation",
+    ];
+
+    pub fn get(&self, name: &str) -> Option<f32> {
+        match name {
+            "diffusion" => Some(self.diffusion),
+            "evaporation" => Some(self.evaporation),
+            "wander_weight" => Some(self.wander_weight),
+            "trail_weight" => Some(self.trail_weight),
+            "max_speed" => Some(self.max_speed),
+            "capacity" => Some(self.capacity),
+            "steering_gain" => Some(self.steering_gain),
+            "wander_amplitude" => Some(self.wander_amplitude),
+            "deposit" => Some(self.deposit),
+            "alarm_weight" => Some(self.alarm_weight),
+            "alarm_deposit" => Some(self.alarm_deposit),
+            "alarm_evaporation" => Some(self.alarm_evaporation),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, value: f32) -> Result<(), String> {
+        let slot = match name {
+            "diffusion" => &mut self.diffusion,
+            "evaporation" => &mut self.evaporation,
+            "wander_weight" => &mut self.wander_weight,
+            "trail_weight" => &mut self.trail_weight,
+            "max_speed" => &mut self.max_speed,
+            "capacity" => &mut self.capacity,
+            "steering_gain" => &mut self.steering_gain,
+            "wander_amplitude" => &mut self.wander_amplitude,
+            "deposit" => &mut self.deposit,
+            "alarm_weight" => &mut self.alarm_weight,
+            "alarm_deposit" => &mut self.alarm_deposit,
+            "alarm_evaporation" => &mut self.alarm_evaporation,
+            _ => return Err(format!("Unknown parameter {name}, expected one of {:?}", Self::NAMES)),
+        };
+        *slot = value;
+        Ok(())
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+
+    pub fn seed_bytes(&self) -> Option<[u8; 32]> {
+        let seed = self.seed?;
+        let mut bytes = [0u8; 32];
+        bytes[..8].copy_from_slice(&seed.to_le_bytes());
+        Some(bytes)
+    }
+}
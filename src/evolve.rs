@@ -0,0 +1,162 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::config::{Config, Params};
+use crate::sweep::{run_once, run_parallel};
+
+struct Gene {
+    name: &'static str,
+    low: f32,
+    high: f32,
+}
+
+impl Gene {
+    fn clamp(&self, value: f32) -> f32 {
+        value.clamp(self.low, self.high)
+    }
+
+    fn span(&self) -> f32 {
+        self.high - self.low
+    }
+}
+
+// The knobs of `Ant::percieve_signals`, `Ant::update` and `Ant::leave_signal`
+// that the optimiser may turn, with the range it searches each one in.
+const GENES: [Gene; 5] = [
+    Gene { name: "wander_weight", low: 0.0, high: 50.0 },
+    Gene { name: "trail_weight", low: 0.0, high: 50.0 },
+    Gene { name: "steering_gain", low: 0.5, high: 20.0 },
+    Gene { name: "wander_amplitude", low: 0.0, high: 4.0 },
+    Gene { name: "deposit", low: 0.0, high: 50.0 },
+];
+
+pub struct EvolveOptions {
+    pub population: usize,
+    pub generations: usize,
+    pub seeds: u64,
+    pub ticks: u64,
+    pub threads: usize,
+}
+
+#[derive(Copy, Clone)]
+struct Individual {
+    params: Params,
+    fitness: f32,
+}
+
+impl Individual {
+    fn random(base: Params, rng: &mut impl Rng) -> Self {
+        let mut params = base;
+        for gene in &GENES {
+            let _ = params.set(gene.name, rng.gen_range(gene.low..=gene.high));
+        }
+        Self { params, fitness: 0.0 }
+    }
+
+    fn gene(&self, gene: &Gene) -> f32 {
+        self.params.get(gene.name).unwrap_or_default()
+    }
+
+    // Blend crossover followed by a small uniform nudge on some genes.
+    fn offspring(a: &Self, b: &Self, rng: &mut impl Rng) -> Self {
+        const MUTATION_RATE: f64 = 0.2;
+        const MUTATION_SCALE: f32 = 0.1;
+
+        let mut params = a.params;
+        for gene in &GENES {
+            let (x, y) = (a.gene(gene), b.gene(gene));
+            let mut value = x + rng.gen_range(-0.25f32..1.25) * (y - x);
+            if rng.gen_bool(MUTATION_RATE) {
+                value += MUTATION_SCALE * gene.span() * rng.gen_range(-1.0f32..1.0);
+            }
+            let _ = params.set(gene.name, gene.clamp(value));
+        }
+        Self { params, fitness: 0.0 }
+    }
+}
+
+fn tournament<'a>(population: &'a [Individual], rng: &mut impl Rng) -> &'a Individual {
+    const SIZE: usize = 3;
+    (0..SIZE)
+        .map(|_| &population[rng.gen_range(0..population.len())])
+        .max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+        .unwrap_or(&population[0])
+}
+
+// Genetic algorithm over GENES. Fitness is food delivered per tick averaged
+// over `seeds` headless runs; every generation gets fresh seeds so nothing can
+// overfit a single layout of random walks.
+pub fn evolve(config: Config, options: &EvolveOptions, out: &Path) -> io::Result<Params> {
+    let base_seed = config.seed.unwrap_or(0);
+    let mut rng = ChaCha8Rng::seed_from_u64(base_seed);
+    let population_size = options.population.max(2);
+    let elite = (population_size / 10).max(1);
+    let seeds = options.seeds.max(1);
+
+    let mut population: Vec<Individual> = (0..population_size)
+        .map(|i| match i {
+            0 => Individual { params: config.params, fitness: 0.0 },
+            _ => Individual::random(config.params, &mut rng),
+        })
+        .collect();
+
+    let mut file = File::create(out)?;
+    let mut header = vec!["generation", "best", "mean"];
+    header.extend(GENES.iter().map(|gene| gene.name));
+    file.write_all(format!("{}\n", header.join(",")).as_bytes())?;
+
+    let mut best = population[0];
+    best.fitness = f32::NEG_INFINITY;
+    for generation in 0..options.generations {
+        let seed_offset = base_seed + generation as u64 * seeds;
+        let jobs: Vec<(Params, u64)> = population
+            .iter()
+            .flat_map(|individual| {
+                (0..seeds).map(move |k| (individual.params, seed_offset + k))
+            })
+            .collect();
+
+        for individual in population.iter_mut() {
+            individual.fitness = 0.0;
+        }
+        run_parallel(
+            &jobs,
+            options.threads,
+            |&(params, seed)| run_once(config.clone(), params, seed, options.ticks),
+            |i, summary| {
+                let individual = &mut population[i / seeds as usize];
+                individual.fitness += summary.delivered / summary.ticks.max(1) as f32 / seeds as f32;
+            },
+        );
+
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        if population[0].fitness > best.fitness {
+            best = population[0];
+        }
+        let mean = population.iter().map(|individual| individual.fitness).sum::<f32>()
+            / population.len() as f32;
+
+        let mut row = vec![
+            generation.to_string(),
+            population[0].fitness.to_string(),
+            mean.to_string(),
+        ];
+        row.extend(GENES.iter().map(|gene| population[0].gene(gene).to_string()));
+        file.write_all(format!("{}\n", row.join(",")).as_bytes())?;
+        println!("generation {generation}: best {} mean {mean}", population[0].fitness);
+
+        let mut next: Vec<Individual> = population[..elite].to_vec();
+        while next.len() < population_size {
+            let a = tournament(&population, &mut rng);
+            let b = tournament(&population, &mut rng);
+            next.push(Individual::offspring(a, b, &mut rng));
+        }
+        population = next;
+    }
+
+    Ok(best.params)
+}
@@ -1,4 +1,5 @@
 use bevy::{prelude::*, reflect::Enum};
+use std::f32::consts::TAU;
 use std::fmt::Debug;
 
 use crate::ant::SignalKind;
@@ -7,6 +8,8 @@ use crate::ant::SignalKind;
 pub struct Signals {
     pub exploring: Vec2Field<SignalKind>,
     pub retrieving: Vec2Field<SignalKind>,
+    // Points away from whatever frightened the ants that laid it.
+    pub alarm: Vec2Field<Danger>,
 }
 
 impl Signals {
@@ -24,12 +27,19 @@ impl Signals {
         }
     }
 
-    pub fn update(&mut self, &dt: &f32) {
-        self.exploring.update(0.01, 0.03, &dt);
-        self.retrieving.update(0.01, 0.03, &dt);
+    pub fn update(&mut self, diffusion_rate: f32, evapouration_rate: f32, alarm_evapouration_rate: f32, &dt: &f32) {
+        self.exploring.update(diffusion_rate, evapouration_rate, &dt);
+        self.retrieving.update(diffusion_rate, evapouration_rate, &dt);
+        self.alarm.update(diffusion_rate, alarm_evapouration_rate, &dt);
     }
 }
 
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum Danger {
+    Alarm
+}
+
 #[derive(Copy, Clone, Debug)]
 #[repr(u8)]
 pub enum FoodType {
@@ -52,10 +62,6 @@ impl Food {
         }
     }
 
-    pub fn get_cells(&mut self) -> &[Cell] {
-        self.amount.get_cells()
-    }
-
     pub fn put(&mut self, area: Rect, depth: f32) {
         let area = Rect::from_center_size(Vec2::ZERO, self.amount.size).intersect(area);
         let along = self.amount.lattice.project_onto(Vec2::X);
@@ -95,18 +101,57 @@ impl Food {
     }
 }
 
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum Terrain {
+    Wall
+}
+
+#[derive(Resource)]
+pub struct Obstacles {
+    pub walls: Field<Terrain>,
+}
+
+impl Obstacles {
+    pub fn new(lattice: Vec2, size: Vec2) -> Self {
+        Self {
+            walls: Field::<Terrain>::new(
+                Terrain::Wall,
+                lattice,
+                size
+            ),
+        }
+    }
+
+    pub fn is_blocked(&self, pos: Vec2) -> bool {
+        self.walls.get_cell_value(pos) > 0.5
+    }
+
+    // The closest spot to `pos` outside the walls, looked for on rings one
+    // lattice cell apart, or None if there is no gap within `reach`.
+    pub fn nearest_free(&self, pos: Vec2, reach: f32) -> Option<Vec2> {
+        let step = self.walls.lattice.min_element();
+        let mut radius = 0.0;
+        while radius <= reach {
+            let around = ((TAU * radius / step).ceil() as usize).max(1);
+            let free = (0..around)
+                .map(|i| pos + radius * Vec2::from_angle(TAU * i as f32 / around as f32))
+                .find(|&spot| !self.is_blocked(spot));
+            if free.is_some() {
+                return free;
+            }
+            radius += step;
+        }
+        None
+    }
+}
+
 #[derive(Copy, Clone, Debug, Component)]
 pub struct Cell {
     pub region: Rect,
     pub val: f32,
 }
 
-impl Cell {
-    pub fn read_from(&mut self, field: & impl Cellular<f32>) {
-        self.val = field.get_cell_value(self.region.center());
-    }
-}
-
 #[derive(Copy, Clone, Debug, Component)]
 pub struct Vec2Cell {
     pub region: Rect,
@@ -183,27 +228,70 @@ impl<T: Copy + Clone + Debug> Vec2Field<T> {
         return self.x.dimensions;
     }
 
+    pub fn get_size(&self) -> Vec2 {
+        return self.x.size;
+    }
+
+    pub fn lattice_idx_to_center(&self, x_idx: usize, y_idx: usize) -> Vec2 {
+        self.x.lattice_idx_to_center(x_idx, y_idx)
+    }
+
     pub fn fill_with(&mut self, generator: &mut dyn FnMut() -> f32) {
+        self.cell_cache.truncate(0);
         self.x.fill_with(generator);
         self.y.fill_with(generator);
     }
 
+    pub fn get_values(&self) -> (&[f32], &[f32]) {
+        (self.x.get_values(), self.y.get_values())
+    }
+
+    pub fn set_values(&mut self, x_values: Vec<f32>, y_values: Vec<f32>) {
+        self.cell_cache.truncate(0);
+        self.x.set_values(x_values);
+        self.y.set_values(y_values);
+    }
+
+    pub fn total_magnitude(&self) -> f32 {
+        self.x.cells
+            .iter()
+            .zip(&self.y.cells)
+            .map(|(&x, &y)| Vec2::new(x, y).length())
+            .sum()
+    }
+
     pub fn set_cell_value(&mut self, value: Vec2, pos: Vec2) {
+        self.cell_cache.truncate(0);
         self.x.set_cell_value(value.x, pos.clone());
         self.y.set_cell_value(value.y, pos);
     }
 
     pub fn acc_cell_value(&mut self, value: Vec2, pos: Vec2) {
+        self.cell_cache.truncate(0);
         self.x.acc_cell_value(value.x, pos.clone());
         self.y.acc_cell_value(value.y, pos);
     }
 
     pub fn set_cell_value_at_lattice_idx(&mut self, value: Vec2, lattice_idx: (usize, usize)) {
+        self.cell_cache.truncate(0);
         self.x.set_cell_value_at_lattice_idx(value.x, lattice_idx);
         self.y.set_cell_value_at_lattice_idx(value.y, lattice_idx);
     }
 
+    pub fn paint(&mut self, center: Vec2, radius: f32, value: Vec2) {
+        self.cell_cache.truncate(0);
+        self.x.paint(center, radius, value.x);
+        self.y.paint(center, radius, value.y);
+    }
+
+    pub fn acc_paint(&mut self, center: Vec2, radius: f32, value: Vec2) {
+        self.cell_cache.truncate(0);
+        self.x.acc_paint(center, radius, value.x);
+        self.y.acc_paint(center, radius, value.y);
+    }
+
     pub fn update(&mut self, diffusion_rate: f32, evapouration_rate: f32, &dt: &f32) {
+        self.cell_cache.truncate(0);
         self.x.update(diffusion_rate, evapouration_rate, &dt);
         self.y.update(diffusion_rate, evapouration_rate, &dt);
     }
@@ -291,13 +379,21 @@ impl<T: Copy + Clone + Debug> Field<T> {
         pos_offset - 0.5 * self.size
     }
 
+    pub fn lattice_idx_to_center(&self, x_idx: usize, y_idx: usize) -> Vec2 {
+        self.lattice_idx_to_pos(x_idx, y_idx) + 0.5 * self.lattice
+    }
+
     fn pos_to_lattice_idx(&self, pos: Vec2) -> (usize, usize) {
         let scale_transform: Mat2 = Mat2::from_diagonal(self.lattice).inverse();
         let cell_pos: IVec2 = scale_transform
             .mul_vec2((0.5 * self.size + pos).max(Vec2::ZERO).min(self.size))
             .as_ivec2();
 
-        (cell_pos.x as usize, cell_pos.y as usize)
+        // the far edges belong to the last cell rather than one past it
+        (
+            (cell_pos.x as usize).min(self.dimensions.w().saturating_sub(1)),
+            (cell_pos.y as usize).min(self.dimensions.h().saturating_sub(1)),
+        )
     }
 
 
@@ -352,6 +448,46 @@ impl<T: Copy + Clone + Debug> Field<T> {
         self.set_cell_value_at_lattice_idx(current + value, lattice_idx);
     }
 
+    // Cells whose centre lies within `radius` of `center`.
+    fn cells_within(&self, center: Vec2, radius: f32) -> Vec<usize> {
+        (0..self.cells.len())
+            .filter(|&i| {
+                let (x, y) = self.dimensions.to_grid(i).unwrap_or((0, 0));
+                self.lattice_idx_to_center(x, y).distance_squared(center) <= radius * radius
+            })
+            .collect()
+    }
+
+    pub fn paint(&mut self, center: Vec2, radius: f32, value: f32) {
+        self.reset_cache();
+        for i in self.cells_within(center, radius) {
+            self.cells[i] = value;
+        }
+    }
+
+    pub fn acc_paint(&mut self, center: Vec2, radius: f32, value: f32) {
+        self.reset_cache();
+        for i in self.cells_within(center, radius) {
+            self.cells[i] += value;
+        }
+    }
+
+    pub fn get_values(&self) -> &[f32] {
+        &self.cells
+    }
+
+    pub fn total(&self) -> f32 {
+        self.cells.iter().sum()
+    }
+
+    pub fn set_values(&mut self, values: Vec<f32>) {
+        if values.len() != self.cells.len() {
+            panic!("Expected {} cell values, got {}.", self.cells.len(), values.len());
+        }
+        self.reset_cache();
+        self.cells = values;
+    }
+
     pub fn fill_with(&mut self, generator: &mut dyn FnMut() -> f32) {
         self.reset_cache();
         for i in 0..self.dimensions.linear_max() {
@@ -414,3 +550,260 @@ impl<T: Copy + Clone + Debug> Cellular<f32> for Field<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Lattice spacings are multiples of a quarter so that size / lattice is
+    // exact and the field has exactly `w` by `h` cells.
+    fn field(w: usize, h: usize, lattice: Vec2) -> Field<FoodType> {
+        Field::new(FoodType::Yummy, lattice, lattice * Vec2::new(w as f32, h as f32))
+    }
+
+    fn lattice() -> impl Strategy<Value = Vec2> {
+        (1u8..32, 1u8..32).prop_map(|(x, y)| Vec2::new(x as f32, y as f32) / 4.0)
+    }
+
+    fn dimensions() -> impl Strategy<Value = (usize, usize)> {
+        (1usize..24, 1usize..24)
+    }
+
+    fn filled(w: usize, h: usize, values: &[f32]) -> Field<FoodType> {
+        let mut field = field(w, h, Vec2::ONE);
+        field.set_values(values[..w * h].to_vec());
+        field
+    }
+
+    fn close(actual: f32, expected: f32, tolerance: f32) -> bool {
+        (actual - expected).abs() <= tolerance * expected.abs().max(1.0)
+    }
+
+    #[test]
+    fn indexer_rejects_out_of_range() {
+        let indexer = LatticeIndexer(4, 3);
+        assert_eq!(indexer.linear_max(), 12);
+        assert_eq!(indexer.to_linear((3, 2)), Some(11));
+        assert_eq!(indexer.to_linear((4, 0)), None);
+        assert_eq!(indexer.to_linear((0, 3)), None);
+        assert_eq!(indexer.to_grid(11), Some((3, 2)));
+        assert_eq!(indexer.to_grid(12), None);
+    }
+
+    #[test]
+    fn dimensions_round_down() {
+        let field = Field::new(FoodType::Yummy, Vec2::new(30.0, 40.0), Vec2::new(100.0, 100.0));
+        assert_eq!((field.dimensions.w(), field.dimensions.h()), (3, 2));
+        assert_eq!(field.get_values().len(), 6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_lattice_panics() {
+        Field::new(FoodType::Yummy, Vec2::new(0.0, 1.0), Vec2::splat(10.0));
+    }
+
+    #[test]
+    fn lattice_positions_span_the_field() {
+        let field = field(8, 4, Vec2::new(2.0, 3.0));
+        assert_eq!(field.lattice_idx_to_pos(0, 0), Vec2::new(-8.0, -6.0));
+        assert_eq!(field.lattice_idx_to_center(0, 0), Vec2::new(-7.0, -4.5));
+        assert_eq!(field.lattice_idx_to_center(7, 3), Vec2::new(7.0, 4.5));
+        // one past the last cell is the far corner
+        assert_eq!(field.lattice_idx_to_pos(8, 4), Vec2::new(8.0, 6.0));
+    }
+
+    #[test]
+    fn positions_outside_are_clamped() {
+        let mut field = field(8, 4, Vec2::ONE);
+        assert_eq!(field.pos_to_lattice_idx(Vec2::new(-100.0, -100.0)), (0, 0));
+        assert_eq!(field.pos_to_lattice_idx(Vec2::new(-100.0, 1.5)), (0, 3));
+        // the far edges land in the last cell, so writes there still count
+        let far = Vec2::new(100.0, 100.0);
+        assert_eq!(field.pos_to_lattice_idx(far), (7, 3));
+        assert_eq!(field.pos_to_lattice_idx(Vec2::new(4.0, 0.0)), (7, 2));
+        field.set_cell_value(1.0, far);
+        field.acc_cell_value(1.0, Vec2::new(4.0, 0.0));
+        assert_eq!(field.get_cell_value(far), 1.0);
+        assert_eq!(field.total(), 2.0);
+        // just inside lands in the same last cell
+        field.set_cell_value(2.0, Vec2::new(3.99, 1.99));
+        assert_eq!(field.get_values()[31], 2.0);
+    }
+
+    #[test]
+    fn nearest_free_steps_out_of_walls() {
+        let mut obstacles = Obstacles::new(Vec2::ONE, Vec2::splat(20.0));
+        for x in -3..3 {
+            for y in -3..3 {
+                obstacles.walls.set_cell_value(1.0, Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+            }
+        }
+        let open = Vec2::new(8.5, 8.5);
+        assert_eq!(obstacles.nearest_free(open, 10.0), Some(open));
+
+        let free = obstacles.nearest_free(Vec2::new(0.5, 0.5), 10.0).unwrap();
+        assert!(!obstacles.is_blocked(free));
+        assert!(free.distance(Vec2::new(0.5, 0.5)) <= 4.0);
+        assert_eq!(obstacles.nearest_free(Vec2::new(0.5, 0.5), 2.0), None);
+    }
+
+    #[test]
+    fn stencil_wraps_around_edges() {
+        let (rate, dt) = (1.0, 0.5);
+        let mut field = field(5, 5, Vec2::ONE);
+        field.set_cell_value_at_lattice_idx(1.0, (0, 0));
+        field.update(rate, 0.0, &dt);
+
+        let b = 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+        let (edge, corner) = (0.25 / (1.0 + b), 0.25 * b / (1.0 + b));
+        let value = |x, y| field.value_lookup(x, y).unwrap();
+        assert!(close(value(0, 0), 1.0 - rate * dt, 1e-6));
+        for (x, y) in [(1, 0), (4, 0), (0, 1), (0, 4)] {
+            assert!(close(value(x, y), rate * dt * edge, 1e-6), "edge ({x}, {y})");
+        }
+        for (x, y) in [(1, 1), (4, 1), (1, 4), (4, 4)] {
+            assert!(close(value(x, y), rate * dt * corner, 1e-6), "corner ({x}, {y})");
+        }
+        assert_eq!(value(2, 2), 0.0);
+        assert_eq!(value(2, 0), 0.0);
+    }
+
+    #[test]
+    fn uniform_field_only_evaporates() {
+        let mut field = field(6, 3, Vec2::ONE);
+        field.fill_with(&mut || 2.0);
+        field.update(0.8, 0.5, &1.0);
+        assert!(field.get_values().iter().all(|&v| close(v, 1.0, 1e-6)));
+    }
+
+    proptest! {
+        #[test]
+        fn indexer_round_trips((w, h) in dimensions(), seed in any::<usize>()) {
+            let indexer = LatticeIndexer(w, h);
+            let linear = seed % indexer.linear_max();
+            let grid = indexer.to_grid(linear).unwrap();
+            prop_assert!(grid.0 < w && grid.1 < h);
+            prop_assert_eq!(indexer.to_linear(grid), Some(linear));
+        }
+
+        #[test]
+        fn pos_round_trips_through_index(
+            (w, h) in dimensions(),
+            lattice in lattice(),
+            seed in any::<(usize, usize)>(),
+            offset in (0.01f32..0.99, 0.01f32..0.99),
+        ) {
+            let field = field(w, h, lattice);
+            let idx = (seed.0 % w, seed.1 % h);
+            let center = field.lattice_idx_to_center(idx.0, idx.1);
+            prop_assert_eq!(field.pos_to_lattice_idx(center), idx);
+            let inside = field.lattice_idx_to_pos(idx.0, idx.1) + Vec2::new(offset.0, offset.1) * lattice;
+            prop_assert_eq!(field.pos_to_lattice_idx(inside), idx);
+        }
+
+        #[test]
+        fn positions_land_in_range(
+            (w, h) in dimensions(),
+            lattice in lattice(),
+            at in (-2f32..2.0, -2f32..2.0),
+        ) {
+            let field = field(w, h, lattice);
+            let (x, y) = field.pos_to_lattice_idx(Vec2::new(at.0, at.1) * field.size);
+            prop_assert!(x < w && y < h);
+        }
+
+        #[test]
+        fn diffusion_conserves_mass(
+            (w, h) in dimensions(),
+            values in prop::collection::vec(0f32..10.0, 24 * 24),
+            rate in 0f32..1.0,
+            dt in 0f32..1.0,
+            steps in 1usize..10,
+        ) {
+            let mut field = filled(w, h, &values);
+            let before = field.total();
+            for _ in 0..steps {
+                field.update(rate, 0.0, &dt);
+            }
+            prop_assert!(close(field.total(), before, 1e-4), "{} != {before}", field.total());
+        }
+
+        #[test]
+        fn evaporation_decays_exponentially(
+            (w, h) in dimensions(),
+            values in prop::collection::vec(0.1f32..10.0, 24 * 24),
+            diffusion in 0f32..1.0,
+            evaporation in 0f32..0.9,
+            dt in 0.01f32..1.0,
+            steps in 1usize..20,
+        ) {
+            let mut field = filled(w, h, &values);
+            let expected = field.total() * (1.0 - evaporation).powf(dt * steps as f32);
+            for _ in 0..steps {
+                field.update(diffusion, evaporation, &dt);
+            }
+            prop_assert!(close(field.total(), expected, 1e-3), "{} != {expected}", field.total());
+        }
+
+        #[test]
+        fn vec2_field_matches_its_components(
+            (w, h) in dimensions(),
+            lattice in lattice(),
+            edits in prop::collection::vec((0u8..4, -0.6f32..0.6, -0.6f32..0.6, -5f32..5.0, -5f32..5.0), 1..20),
+            dt in 0f32..1.0,
+        ) {
+            let size = lattice * Vec2::new(w as f32, h as f32);
+            let mut vectors = Vec2Field::new(FoodType::Yummy, lattice, size);
+            let (mut xs, mut ys) = (field(w, h, lattice), field(w, h, lattice));
+            // fill the cell cache so a stale one would show
+            vectors.get_cells();
+
+            for &(kind, px, py, vx, vy) in &edits {
+                let pos = Vec2::new(px, py) * size;
+                let value = Vec2::new(vx, vy);
+                match kind {
+                    0 => {
+                        vectors.set_cell_value(value, pos);
+                        xs.set_cell_value(vx, pos);
+                        ys.set_cell_value(vy, pos);
+                    }
+                    1 => {
+                        vectors.acc_cell_value(value, pos);
+                        xs.acc_cell_value(vx, pos);
+                        ys.acc_cell_value(vy, pos);
+                    }
+                    2 => {
+                        vectors.acc_paint(pos, lattice.max_element(), value);
+                        xs.acc_paint(pos, lattice.max_element(), vx);
+                        ys.acc_paint(pos, lattice.max_element(), vy);
+                    }
+                    _ => {
+                        vectors.update(0.5, 0.1, &dt);
+                        xs.update(0.5, 0.1, &dt);
+                        ys.update(0.5, 0.1, &dt);
+                    }
+                }
+                prop_assert_eq!(vectors.get_cell_value(pos), Vec2::new(xs.get_cell_value(pos), ys.get_cell_value(pos)));
+            }
+
+            prop_assert_eq!(vectors.get_values(), (xs.get_values(), ys.get_values()));
+            let magnitude: f32 = xs
+                .get_values()
+                .iter()
+                .zip(ys.get_values())
+                .map(|(&x, &y)| x.hypot(y))
+                .sum();
+            prop_assert!(close(vectors.total_magnitude(), magnitude, 1e-5));
+            let cells: Vec<Vec2> = vectors.get_cells().iter().map(|cell| cell.val).collect();
+            let expected: Vec<Vec2> = xs
+                .get_values()
+                .iter()
+                .zip(ys.get_values())
+                .map(|(&x, &y)| Vec2::new(x, y))
+                .collect();
+            prop_assert_eq!(cells, expected);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// A fixed patch of ground that kills ants crossing it at `lethality` deaths
+// per second, e.g. a puddle or a spider's web.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Hazard {
+    pub center: [f32; 2],
+    pub radius: f32,
+    pub lethality: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Predators {
+    pub count: usize,
+    pub speed: f32,
+    pub radius: f32,
+    // How far away a predator notices ants and starts chasing them.
+    pub sight: f32,
+    pub lethality: f32,
+}
+
+impl Default for Predators {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            speed: 120.0,
+            radius: 15.0,
+            sight: 150.0,
+            lethality: 5.0,
+        }
+    }
+}
+
+// Anything that can hurt an ant, whether it moves or not.
+#[derive(Clone, Copy, Debug)]
+pub struct Threat {
+    pub center: Vec2,
+    pub radius: f32,
+    pub lethality: f32,
+}
+
+impl Threat {
+    pub fn from_hazard(hazard: &Hazard) -> Self {
+        Self {
+            center: Vec2::from_array(hazard.center),
+            radius: hazard.radius,
+            lethality: hazard.lethality,
+        }
+    }
+
+    // Ants inside `radius` may be killed, those within twice that only get a fright.
+    pub fn reaches(&self, pos: Vec2) -> bool {
+        (pos - self.center).length_squared() < self.radius * self.radius
+    }
+
+    pub fn frightens(&self, pos: Vec2) -> bool {
+        (pos - self.center).length_squared() < 4.0 * self.radius * self.radius
+    }
+
+    // Chance of dying during a step of `dt` seconds spent within reach.
+    pub fn kill_chance(&self, dt: f32) -> f64 {
+        (1.0 - (-self.lethality * dt).exp()).clamp(0.0, 1.0) as f64
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Predator {
+    pub position: Vec2,
+    pub velocity: Vec2,
+}
+
+impl Predator {
+    pub fn new(position: Vec2, heading: f32) -> Self {
+        Self {
+            position,
+            velocity: Vec2::from_angle(heading),
+        }
+    }
+
+    pub fn threat(&self, predators: &Predators) -> Threat {
+        Threat {
+            center: self.position,
+            radius: predators.radius,
+            lethality: predators.lethality,
+        }
+    }
+
+    // Heads for the nearest ant in sight, otherwise wanders.
+    pub fn update(&mut self, prey: Option<Vec2>, predators: &Predators, rng: &mut impl Rng, &dt: &f32) {
+        let desired = match prey {
+            Some(target) if (target - self.position).length() < predators.sight => {
+                (target - self.position).normalize_or_zero()
+            }
+            _ => self.velocity.rotate(Vec2::from_angle(rng.gen_range(-0.5f32..0.5))),
+        };
+        if desired != Vec2::ZERO {
+            self.velocity = desired.normalize();
+        }
+        self.position += predators.speed * dt * self.velocity;
+    }
+
+    pub fn reach_around(&mut self, rect: Rect) {
+        self.position = rect.min + (self.position - rect.min).rem_euclid(rect.size());
+    }
+}
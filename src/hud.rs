@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::config::Params;
+use crate::metrics::Metrics;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Component)]
+pub enum Sparkline {
+    DeliveryRate,
+    PheromoneMass,
+}
+
+impl Sparkline {
+    pub const ALL: [Sparkline; 2] = [Sparkline::DeliveryRate, Sparkline::PheromoneMass];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Sparkline::DeliveryRate => "delivery rate",
+            Sparkline::PheromoneMass => "pheromone mass",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct SparkBar {
+    pub line: Sparkline,
+    pub index: usize,
+}
+
+#[derive(Component)]
+pub struct HudText;
+
+#[derive(Component)]
+pub struct HudRoot;
+
+#[derive(Resource, Default)]
+pub struct Hud {
+    // Tick and food delivered at the last sample.
+    last: Option<(u64, f32)>,
+    pub rate: f32,
+    rates: VecDeque<f32>,
+    masses: VecDeque<f32>,
+}
+
+impl Hud {
+    pub const SAMPLES: usize = 60;
+    pub const SAMPLE_TICKS: u64 = 32;
+
+    // Takes a sample every SAMPLE_TICKS ticks; `timestep` is seconds per tick.
+    pub fn sample(&mut self, metrics: &Metrics, timestep: f32) {
+        let (last_tick, last_delivered) = *self.last.get_or_insert((metrics.tick, metrics.delivered));
+        if metrics.tick < last_tick + Self::SAMPLE_TICKS {
+            // a loaded snapshot can send the tick backwards
+            if metrics.tick < last_tick {
+                self.last = None;
+            }
+            return;
+        }
+        let seconds = (metrics.tick - last_tick) as f32 * timestep;
+        self.rate = (metrics.delivered - last_delivered) / seconds;
+        self.last = Some((metrics.tick, metrics.delivered));
+
+        for (history, value) in [
+            (&mut self.rates, self.rate),
+            (&mut self.masses, metrics.exploring_mass + metrics.retrieving_mass),
+        ] {
+            history.push_back(value);
+            while history.len() > Self::SAMPLES {
+                history.pop_front();
+            }
+        }
+    }
+
+    pub fn history(&self, line: Sparkline) -> &VecDeque<f32> {
+        match line {
+            Sparkline::DeliveryRate => &self.rates,
+            Sparkline::PheromoneMass => &self.masses,
+        }
+    }
+
+    // Height of a bar as a fraction of the tallest value shown.
+    pub fn bar(&self, line: Sparkline, index: usize) -> f32 {
+        let history = self.history(line);
+        let max = history.iter().cloned().fold(0.0, f32::max);
+        // the newest sample is always in the rightmost bar
+        let offset = Self::SAMPLES - history.len();
+        match (index.checked_sub(offset), max > 0.0) {
+            (Some(i), true) => (history[i] / max).max(0.0),
+            _ => 0.0,
+        }
+    }
+}
+
+pub fn text(metrics: &Metrics, rate: f32, fps: Option<f64>, speed: f32, paused: bool, params: &Params) -> String {
+    let mut text = String::new();
+    let fps = fps.map_or("-".to_string(), |fps| format!("{fps:.0}"));
+    let _ = writeln!(
+        text,
+        "FPS {fps}   tick {}   {}",
+        metrics.tick,
+        if paused { "paused".to_string() } else { format!("{speed}x") },
+    );
+    let _ = writeln!(
+        text,
+        "ants {}: {} exploring, {} retrieving",
+        metrics.exploring_ants + metrics.retrieving_ants,
+        metrics.exploring_ants,
+        metrics.retrieving_ants,
+    );
+    let _ = writeln!(
+        text,
+        "food {:.0}   delivered {:.1}   rate {rate:.2}/s   deaths {}",
+        metrics.food, metrics.delivered, metrics.deaths,
+    );
+    for name in Params::NAMES {
+        let _ = writeln!(text, "{name} {}", params.get(name).unwrap_or_default());
+    }
+    text
+}
@@ -0,0 +1,62 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+
+use crate::ant::{Ant, Caste, Perception, Traits};
+use crate::Inventory;
+
+// Marks the ant the inspector panel shows.
+#[derive(Component)]
+pub struct Inspected;
+
+#[derive(Component)]
+pub struct InspectorPanel;
+
+#[derive(Component)]
+pub struct InspectorText;
+
+#[derive(Resource, Default)]
+pub struct Inspector {
+    pub follow: bool,
+}
+
+pub fn text(
+    ant: &Ant,
+    inventory: &Inventory,
+    traits: &Traits,
+    caste: Caste,
+    perception: &Perception,
+    follow: bool,
+) -> String {
+    let mut text = String::new();
+    let _ = writeln!(text, "{caste:?} ant, {:?}{}", ant.state, if follow { ", following" } else { "" });
+    let _ = writeln!(text, "position {:.1?}", ant.position);
+    let _ = writeln!(text, "velocity {:.3?}   speed {:.1}", ant.velocity, ant.speed);
+    let _ = writeln!(text, "age {:.1}s", ant.age);
+    let _ = writeln!(text, "carrying {:.2} of {:.2}", inventory.contents, inventory.capacity);
+    let _ = writeln!(
+        text,
+        "traits: speed {:.1}, wander {:.2} x {:.1}, trail {:.1}, alarm {:.1}, sensing {:.0}",
+        traits.max_speed,
+        traits.wander_amplitude,
+        traits.wander_weight,
+        traits.trail_weight,
+        traits.alarm_weight,
+        traits.sensing_range,
+    );
+    let _ = writeln!(text, "sensed at {:.1?}", perception.sensor);
+    let _ = writeln!(text, "  exploring {:.2?}", perception.exploring);
+    let _ = writeln!(text, "  retrieving {:.2?}", perception.retrieving);
+    let _ = writeln!(text, "  alarm {:.2?}", perception.alarm);
+    let _ = writeln!(text, "term      weight    gas  steering");
+    for (name, (weight, gas, steering)) in Perception::TERMS.iter().zip(perception.terms) {
+        let _ = writeln!(text, "{name:<8} {weight:>7.2} {gas:>6.2} {:>8.1}°", steering.to_degrees());
+    }
+    let _ = write!(
+        text,
+        "total            {:>6.2} {:>8.1}°",
+        perception.gas,
+        perception.steering.to_degrees(),
+    );
+    text
+}
@@ -1,26 +1,161 @@
 mod ant;
+mod batch;
+mod cli;
+mod config;
+mod evolve;
 mod field;
+mod hazard;
+mod hud;
+mod inspect;
+mod map;
+mod metrics;
+mod nest;
+mod overlay;
+mod raster;
+#[cfg(test)]
+mod regression;
+mod replay;
+mod snapshot;
+mod sweep;
+mod texture;
+mod tools;
+mod trail;
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
 
 use ant::Ant;
+use ant::Caste;
+use ant::Perception;
 use ant::SignalKind;
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use ant::Traits;
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}};
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::render::view::NoFrustumCulling;
+use bevy::time::TimeUpdateStrategy;
+use batch::AntRendering;
+use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin};
+use bevy::ecs::query::Has;
+use bevy::ecs::system::{SystemParam, SystemState};
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
 use bevy::window::PrimaryWindow;
 use bevy_prng::ChaCha8Rng;
 use bevy_rand::prelude::*;
-use field::{Food, Cellular, Cell};
+use clap::Parser;
+use cli::{Cli, Command, Images, Outputs};
+use config::Config;
+use evolve::EvolveOptions;
+use field::{Food, Cellular, Obstacles};
 use field::Signals;
-use field::Vec2Field;
+use field::{Danger, Vec2Field};
+use hazard::{Hazard, Predator, Predators, Threat};
+use hud::{Hud, HudRoot, HudText, SparkBar, Sparkline};
+use inspect::{Inspected, Inspector, InspectorPanel, InspectorText};
+use map::WorldMap;
+use metrics::{Casualties, FoodDelivered, Metrics, MetricsLog};
+use nest::{Nest, Nests};
+use raster::{Canvas, Scene};
+use overlay::{Overlay, OverlayMode};
+use replay::{AntFrame, Player, Recorder};
+use snapshot::Snapshot;
+use sweep::SweepSpec;
+use texture::FieldLayer;
+use tools::{Tool, Toolbox};
+use trail::{Footfall, Trail, TrailMode};
 use rand_core::RngCore;
 
 const RESOLUTION: (f32, f32) = (1920f32, 1080f32);
+const BACKGROUND: Color = Color::rgb(0.1, 0.25, 0.0);
 
 const NEST_CENTER: Vec2 = Vec2 { x: 576f32, y: 324f32 };
 const NEST_RADIUS_SQ: f32 = 10000.0;
 const MAX_FOOD_HEIGHT: f32 = 10.0;
+const SNAPSHOT_PATH: &str = "snapshot.ron";
+const SPEEDS: (f32, f32) = (0.25, 16.0);
+const ZOOMS: (f32, f32) = (0.05, 10.0);
+// Random spots tried around a nest before an ant is put at the nearest gap in
+// the walls instead, and how far away that gap may be.
+const SPAWN_TRIES: usize = 16;
+const WALL_ESCAPE: f32 = 400.0;
+
+#[derive(Resource, Default)]
+struct Tick(u64);
+
+// Ticks asked for with single-step while paused, run outside the fixed loop.
+#[derive(Resource, Default)]
+struct PendingSteps(u32);
+
+#[derive(Component)]
+struct Scenery;
+
+#[derive(Component)]
+struct OverlaySprite;
+
+#[derive(Component)]
+struct AntBatch;
+
+#[derive(Resource, Default)]
+struct DebugGizmos {
+    enabled: bool,
+}
+
+// One of the sampled ants that debug gizmos are drawn for.
+#[derive(Component)]
+struct Debugged;
+
+#[derive(Resource)]
+struct Recording(Recorder);
+
+// Where F5 saves a snapshot and F9 loads it from.
+#[derive(Resource)]
+struct SnapshotFile(PathBuf);
 
+// A snapshot to start from instead of the colony spawned at startup.
+#[derive(Resource)]
+struct Resume(PathBuf);
 
-fn is_in_nest(&pos: &Vec2) -> bool {
-    (pos - NEST_CENTER).length_squared() < NEST_RADIUS_SQ 
+// The first output that could not be written, so a headless run can fail
+// instead of quietly leaving a short file behind.
+#[derive(Resource, Default)]
+struct OutputFailure(Option<String>);
+
+impl OutputFailure {
+    fn report(&mut self, message: String) {
+        error!("{message}");
+        self.0.get_or_insert(message);
+    }
+}
+
+#[derive(Resource)]
+struct FrameExport {
+    images: Images,
+    count: u64,
+}
+
+impl FrameExport {
+    // Where frame `n` of the image sequence goes, if it is kept at all.
+    fn frame_path(&self, n: u64) -> Option<PathBuf> {
+        let dir = self.images.frames.as_ref()?;
+        match n % self.images.every.max(1) {
+            0 => Some(dir.join(format!("frame_{n:06}.png"))),
+            _ => None,
+        }
+    }
+}
+
+// Where the mouse points in world coordinates, for every tool to share.
+#[derive(Resource, Default)]
+struct Cursor {
+    world: Option<Vec2>,
+}
+
+#[derive(Resource)]
+struct Replay {
+    player: Player,
+    frame: usize,
+    playing: bool,
+    sprites: Vec<Entity>,
 }
 
 
@@ -57,21 +192,96 @@ impl Inventory {
         }
     }
 
-    pub fn dropoff(&mut self, position: Vec2, sink: &mut Food) {
+    pub fn dropoff(&mut self, position: Vec2, sink: &mut Food) -> f32 {
         let current = sink.amount.get_cell_value(position);
         let available = self.contents.clamp(0., MAX_FOOD_HEIGHT - current);
         sink.deposit_into(position, available);
         self.contents -= available;
+        available
     }
 }
 
 fn main() {
-    sim();
+    let cli = Cli::parse();
+    let config = cli.config();
+    match cli.command.unwrap_or(Command::Run(Outputs::default())) {
+        Command::Run(outputs) => windowed(config, outputs),
+        Command::Headless { ticks, out, record, images, resume, save_snapshot } => {
+            headless(config, ticks, Outputs { record, metrics: out, images, resume, save_snapshot });
+        }
+        Command::Replay { file } => replay(config, file),
+        Command::Bench { ticks } => bench(config, ticks),
+        Command::Sweep { spec, ticks, seeds, threads, out } => {
+            let spec = SweepSpec::load(&spec)
+                .unwrap_or_else(|err| panic!("Could not load sweep spec from {spec:?}: {err}"));
+            let threads = threads.unwrap_or_else(default_threads);
+            if let Err(err) = sweep::sweep(config, &spec, seeds, ticks, threads, &out) {
+                panic!("Sweep failed: {err}");
+            }
+        }
+        Command::Evolve { ticks, population, generations, seeds, threads, out } => {
+            let options = EvolveOptions {
+                population,
+                generations,
+                seeds,
+                ticks,
+                threads: threads.unwrap_or_else(default_threads),
+            };
+            match evolve::evolve(config, &options, &out) {
+                Ok(best) => println!(
+                    "Best parameters:\n{}",
+                    ron::ser::to_string_pretty(&best, ron::ser::PrettyConfig::default())
+                        .unwrap_or_else(|_| format!("{best:?}")),
+                ),
+                Err(err) => panic!("Evolution failed: {err}"),
+            }
+        }
+    }
+}
+
+fn default_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
 }
 
-fn sim() {
-    App::new()
-        .insert_resource(ClearColor(Color::rgb(0.1, 0.25, 0.0)))
+fn default_world() -> WorldMap {
+    let mut food = Food::new(
+        Vec2::splat(10.0),
+        Vec2::new(RESOLUTION.0, RESOLUTION.1),
+    );
+    let food_places = [
+        Rect::from_center_size(Vec2::ZERO, Vec2::splat(400.)),
+    ];
+    let food_depth = 10.;
+    for area in food_places {
+        food.put(area, food_depth);
+    }
+
+    WorldMap {
+        food,
+        obstacles: Obstacles::new(
+            Vec2::splat(10.0),
+            Vec2::new(RESOLUTION.0, RESOLUTION.1),
+        ),
+        nests: None,
+    }
+}
+
+// Resources shared by every mode, windowed or not.
+fn world_app(config: &Config) -> App {
+    let world = match &config.map {
+        Some(path) => WorldMap::load(
+            path,
+            Vec2::splat(10.0),
+            Vec2::new(RESOLUTION.0, RESOLUTION.1),
+            MAX_FOOD_HEIGHT,
+        )
+        .unwrap_or_else(|err| panic!("Could not load map from {path:?}: {err}")),
+        None => default_world(),
+    };
+
+    let mut app = App::new();
+    app
+        .insert_resource(config.clone())
         .insert_resource(Signals {
             exploring: Vec2Field::new(
                 SignalKind::Exploring,
@@ -83,196 +293,1701 @@ fn sim() {
                 Vec2::splat(10.0),
                 Vec2::new(RESOLUTION.0, RESOLUTION.1),
             ),
-        })
-        .insert_resource(
-            Food::new(
+            alarm: Vec2Field::new(
+                Danger::Alarm,
                 Vec2::splat(10.0),
                 Vec2::new(RESOLUTION.0, RESOLUTION.1),
-            )
+            ),
+        })
+        .insert_resource(world.food)
+        .insert_resource(world.obstacles)
+        .insert_resource(
+            world.nests.unwrap_or(Nests::single(NEST_CENTER, NEST_RADIUS_SQ.sqrt()))
         )
-        .add_plugins(EntropyPlugin::<ChaCha8Rng>::default())
+        .add_plugins(match config.seed_bytes() {
+            Some(seed) => EntropyPlugin::<ChaCha8Rng>::with_seed(seed),
+            None => EntropyPlugin::<ChaCha8Rng>::default(),
+        })
+        .init_resource::<Tick>()
+        .init_resource::<FoodDelivered>()
+        .init_resource::<Casualties>();
+    app
+}
+
+// Adds the simulation itself on top of `world_app`.
+fn sim_app(config: &Config, outputs: Outputs) -> App {
+    let mut app = world_app(config);
+    app
+        .init_resource::<OutputFailure>()
+        .add_systems(
+            PreStartup,
+            (
+                spawn_predators,
+                spawn_ants,
+                apply_deferred,
+                resume_snapshot.run_if(resource_exists::<Resume>()),
+            ).chain(),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                take_food,
+                update_ants,
+                move_predators,
+                threaten_ants,
+                leave_signals,
+                age_ants,
+                count_ticks,
+                record_ants.run_if(resource_exists::<Recording>()),
+                write_metrics.run_if(resource_exists::<MetricsLog>()),
+            ).chain(),
+        );
+
+    if let Some(path) = outputs.resume {
+        app.insert_resource(Resume(path));
+    }
+    if let Some(path) = outputs.record {
+        let recorder = Recorder::create(&path)
+            .unwrap_or_else(|err| panic!("Could not create recording {path:?}: {err}"));
+        app.insert_resource(Recording(recorder));
+    }
+    if let Some(path) = outputs.metrics {
+        let log = MetricsLog::create(&path)
+            .unwrap_or_else(|err| panic!("Could not create metrics file {path:?}: {err}"));
+        app.insert_resource(log);
+    }
+    if let Some(dir) = &outputs.images.frames {
+        std::fs::create_dir_all(dir)
+            .unwrap_or_else(|err| panic!("Could not create frame directory {dir:?}: {err}"));
+    }
+    app.insert_resource(FrameExport {
+        images: outputs.images,
+        count: 0,
+    });
+    app
+}
+
+fn add_window(app: &mut App) {
+    app
+        .insert_resource(ClearColor(BACKGROUND))
         .add_plugins(DefaultPlugins)
+        .init_resource::<Cursor>()
         .add_systems(Startup, setup)
-        .add_systems(Update, update_ants)
-        .add_systems(Update, leave_signals)
-        .add_systems(Update, take_food)
-        .add_systems(Update, update_cells)
-        .add_systems(Update, put_food)
+        .add_systems(PreUpdate, track_cursor)
+        .add_systems(Update, (update_field_textures, zoom_camera, pan_camera, fit_camera));
+}
+
+fn windowed(config: Config, outputs: Outputs) {
+    if !batch::gpu_available() {
+        // a window would only fail further in, so say what to run instead
+        eprintln!("No GPU adapter found, so there is nothing to draw a window with.");
+        eprintln!("Run `ants headless --ticks N` instead, with --screenshot or --frames for images.");
+        std::process::exit(1);
+    }
+    let snapshot = outputs.save_snapshot.clone().unwrap_or_else(|| PathBuf::from(SNAPSHOT_PATH));
+    let mut app = sim_app(&config, outputs);
+    add_window(&mut app);
+    app
+        .insert_resource(SnapshotFile(snapshot))
+        .init_resource::<Toolbox>()
+        .init_resource::<Hud>()
+        .init_resource::<Inspector>()
+        .init_resource::<DebugGizmos>()
+        .init_resource::<PendingSteps>()
+        .add_systems(Update, (toggle_debug_gizmos, draw_debug_gizmos).chain())
+        .add_plugins(FrameTimeDiagnosticsPlugin)
+        .add_systems(Startup, (setup_overlay, setup_hud, setup_inspector))
+        .add_systems(Update, (pick_ant, follow_inspected, show_inspector).chain())
+        .add_systems(Update, (toggle_hud, update_hud))
+        .add_systems(Update, (control_overlay, show_overlay).chain())
+        .add_systems(Update, (select_tool, paint_fields, edit_nests, edit_ants, redraw_scenery).chain())
+        .add_systems(Update, (save_snapshot, load_snapshot, capture_window))
+        .add_systems(Update, (control_time, step_paused).chain());
+    match config.ant_rendering {
+        AntRendering::Sprites => {
+            app.add_systems(Update, (dress_ants, apply_deferred, move_ant_sprites).chain());
+        }
+        AntRendering::Batched => {
+            app
+                .add_systems(Startup, setup_ant_batch)
+                .add_systems(Update, draw_ant_batch);
+        }
+    }
+    match config.trails.mode {
+        TrailMode::Off => (),
+        TrailMode::Polylines => {
+            app
+                .add_systems(FixedUpdate, extend_trails.after(update_ants))
+                .add_systems(Update, draw_trails);
+        }
+        TrailMode::Footfall => {
+            app
+                .insert_resource(Footfall::new(
+                    Vec2::splat(10.0),
+                    Vec2::new(RESOLUTION.0, RESOLUTION.1),
+                ))
+                .add_systems(Startup, setup_footfall)
+                .add_systems(FixedUpdate, tread_footfall.after(update_ants));
+        }
+    }
+    app.run();
+}
+
+fn replay(config: Config, path: PathBuf) {
+    let mut app = world_app(&config);
+    add_window(&mut app);
+    // opened once logging is up, so warnings about the file are seen
+    let player = Player::open(&path)
+        .unwrap_or_else(|err| panic!("Could not open recording {path:?}: {err}"));
+    app
+        .insert_resource(Replay {
+            player,
+            frame: 0,
+            playing: true,
+            sprites: vec![],
+        })
+        .add_systems(FixedUpdate, advance_replay)
+        .add_systems(Update, (scrub_replay, show_replay).chain())
         .run();
 }
 
+// Steps the simulation one fixed tick per update, as fast as it will go.
+fn headless(config: Config, ticks: u64, outputs: Outputs) -> App {
+    let screenshot = outputs.images.screenshot.clone();
+    let pheromones = outputs.images.pheromones;
+    let save_snapshot = outputs.save_snapshot.clone();
+    let mut app = sim_app(&config, outputs);
+    app
+        .add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(
+            Time::<Fixed>::default().timestep(),
+        ))
+        .add_systems(FixedUpdate, capture_raster.after(count_ticks));
+    app.finish();
+    app.cleanup();
+
+    while app.world.resource::<Tick>().0 < ticks && app.world.resource::<OutputFailure>().0.is_none() {
+        app.update();
+    }
+    if let Some(err) = &app.world.resource::<OutputFailure>().0 {
+        panic!("{err}");
+    }
+
+    if let Some(path) = screenshot {
+        let frames = ant_frames(&mut app.world);
+        let world = &app.world;
+        raster_frame(
+            world.resource::<Food>(),
+            world.resource::<Obstacles>(),
+            world.resource::<Nests>(),
+            world.resource::<Config>(),
+            pheromones.then(|| world.resource::<Signals>()),
+            &frames,
+        )
+        .save(&path)
+            .unwrap_or_else(|err| panic!("Could not save screenshot to {path:?}: {err}"));
+    }
+    if let Some(path) = save_snapshot {
+        let mut state = SystemState::<SimState>::new(&mut app.world);
+        state
+            .get_mut(&mut app.world)
+            .capture()
+            .save(&path)
+            .unwrap_or_else(|err| panic!("Could not save snapshot to {path:?}: {err}"));
+    }
+    app
+}
+
+fn ant_frames(world: &mut World) -> Vec<AntFrame> {
+    let mut ants = world.query::<(&Ant, &Caste)>();
+    ants.iter(world)
+        .map(|(ant, &caste)| AntFrame::capture(ant, caste))
+        .collect()
+}
+
+fn raster_frame(
+    food: &Food,
+    obstacles: &Obstacles,
+    nests: &Nests,
+    config: &Config,
+    signals: Option<&Signals>,
+    ants: &[AntFrame],
+) -> Canvas {
+    let scene = Scene {
+        food,
+        max_food: MAX_FOOD_HEIGHT,
+        obstacles,
+        nests,
+        hazards: &config.hazards,
+        signals: signals.map(|signals| (signals, default())),
+        ants,
+    };
+    Canvas::render(Vec2::new(RESOLUTION.0, RESOLUTION.1), BACKGROUND, &scene)
+}
+
+fn bench(config: Config, ticks: u64) {
+    let ants = config.ants;
+    let start = Instant::now();
+    headless(config, ticks, Outputs::default());
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{ticks} ticks with {ants} ants in {elapsed:.2}s ({:.1} ticks/s)",
+        ticks as f64 / elapsed,
+    );
+}
+
 fn setup(
     mut windows: Query<&mut Window>,
     mut commands: Commands,
-    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
-    mut food: ResMut<Food>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    food: Res<Food>,
+    obstacles: Res<Obstacles>,
+    nests: Res<Nests>,
+    config: Res<Config>,
+    meshes: ResMut<Assets<Mesh>>,
+    materials: ResMut<Assets<ColorMaterial>>,
 ) {
     let mut primary_window = windows.single_mut();
 
-    primary_window.resolution.set(RESOLUTION.0, RESOLUTION.1);
+    let (width, height) = config.window;
+    primary_window.resolution.set(width, height);
     primary_window
         .resolution
-        .set_physical_resolution(RESOLUTION.0 as u32, RESOLUTION.1 as u32);
+        .set_physical_resolution(width as u32, height as u32);
     commands.spawn(Camera2dBundle::default());
-    for _ in 0..1000 {
-        commands.spawn(EntityFactories::ant_factory(&mut rng));
-    }
-    let food_places = [
-        Rect::from_center_size(Vec2::ZERO, Vec2::splat(400.)),
-    ];
-    let food_depth = 10.;
-    for area in food_places {
-        food.put(area, food_depth);
-    }
 
-    let cells = food.get_cells();
-    for cell in cells {
+    // one texture per field, filled in by `update_field_textures`
+    for (layer, dimensions, size, depth) in [
+        (FieldLayer::Food, food.amount.dimensions, food.amount.size, -0.1),
+        (FieldLayer::Walls, obstacles.walls.dimensions, obstacles.walls.size, -0.2),
+    ] {
         commands.spawn((
-            cell.clone(),
+            layer,
             SpriteBundle {
+                texture: images.add(texture::field_image(dimensions)),
                 sprite: Sprite {
-                    rect: Some(cell.region),
-                    color: Color::rgba(0.7, 0.7, 0.0, cell.val/10.0),
+                    custom_size: Some(size),
                     ..default()
                 },
-                transform: Transform::from_xyz(cell.region.center().x, cell.region.center().y, -0.1),
+                transform: Transform::from_xyz(0.0, 0.0, depth),
                 ..default()
-            }
+            },
         ));
     }
 
-    commands.spawn(MaterialMesh2dBundle {
-        mesh: meshes.add(shape::Circle::new(NEST_RADIUS_SQ.sqrt()).into()).into(),
-        material: materials.add(ColorMaterial::from(Color::PURPLE)),
-        transform: Transform::from_translation(NEST_CENTER.extend(-0.5)),
-        ..default()
+    spawn_scenery(&mut commands, &nests, &config.hazards, meshes, materials);
+}
+
+fn setup_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    signals: Res<Signals>,
+) {
+    let image = images.add(texture::field_image(signals.exploring.get_dimensions()));
+    commands.spawn((
+        OverlaySprite,
+        SpriteBundle {
+            texture: image.clone(),
+            sprite: Sprite {
+                custom_size: Some(signals.exploring.get_size()),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, -0.05),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+    commands.insert_resource(Overlay {
+        mode: OverlayMode::default(),
+        scale: default(),
+        image,
     });
 }
 
+fn setup_hud(mut commands: Commands) {
+    const BAR_WIDTH: f32 = 3.0;
+    const SPARK_HEIGHT: f32 = 30.0;
+
+    let style = TextStyle {
+        font_size: 16.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    commands
+        .spawn((
+            HudRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+        ))
+        .with_children(|root| {
+            root.spawn((HudText, TextBundle::from_section("", style.clone())));
+            for line in Sparkline::ALL {
+                root.spawn(TextBundle::from_section(line.label(), style.clone()));
+                root.spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::FlexEnd,
+                        height: Val::Px(SPARK_HEIGHT),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row| {
+                    for index in 0..Hud::SAMPLES {
+                        row.spawn((
+                            SparkBar { line, index },
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(BAR_WIDTH),
+                                    height: Val::Percent(0.0),
+                                    ..default()
+                                },
+                                background_color: Color::YELLOW.into(),
+                                ..default()
+                            },
+                        ));
+                    }
+                });
+            }
+        });
+}
+
+fn setup_inspector(mut commands: Commands) {
+    commands
+        .spawn((
+            InspectorPanel,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ))
+        .with_children(|panel| {
+            panel.spawn((
+                InspectorText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+            ));
+        });
+}
+
+fn setup_footfall(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    footfall: Res<Footfall>,
+) {
+    commands.spawn((
+        FieldLayer::Footfall,
+        SpriteBundle {
+            texture: images.add(texture::field_image(footfall.steps.dimensions)),
+            sprite: Sprite {
+                custom_size: Some(footfall.steps.size),
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, -0.08),
+            ..default()
+        },
+    ));
+}
+
+fn setup_ant_batch(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn((
+        AntBatch,
+        // the mesh moves every frame, so its bounding box would go stale
+        NoFrustumCulling,
+        MaterialMesh2dBundle {
+            mesh: meshes.add(batch::empty_mesh()).into(),
+            material: materials.add(ColorMaterial::from(Color::WHITE)),
+            ..default()
+        },
+    ));
+}
+
+fn spawn_ants(
+    mut commands: Commands,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    nests: Res<Nests>,
+    obstacles: Res<Obstacles>,
+    config: Res<Config>,
+) {
+    let traits = Traits::from_params(&config.params);
+    for i in 0..config.ants {
+        let nest = &nests.nests[i % nests.nests.len()];
+        let traits = traits.vary(config.trait_spread, &mut *rng);
+        let caste = config.castes.pick(rng.next_u32() as f32 / u32::MAX as f32);
+        commands.spawn(EntityFactories::ant_factory(&mut rng, nest, &obstacles, config.params.capacity, traits, caste));
+    }
+}
+
+fn spawn_predators(
+    mut commands: Commands,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    config: Res<Config>,
+) {
+    let res = Vec2::new(RESOLUTION.0, RESOLUTION.1);
+    for _ in 0..config.predators.count {
+        let position = 0.8 * res * (Vec2::new(rng.next_u32() as f32, rng.next_u32() as f32) / u32::MAX as f32 - 0.5);
+        let heading = 360f32.to_radians() * (rng.next_u32() as f32 / u32::MAX as f32);
+        commands.spawn(EntityFactories::predator_bundle(Predator::new(position, heading), &config.predators));
+    }
+}
+
+fn spawn_scenery(
+    commands: &mut Commands,
+    nests: &Nests,
+    hazards: &[Hazard],
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let material = materials.add(ColorMaterial::from(Color::PURPLE));
+    for nest in &nests.nests {
+        commands.spawn((
+            Scenery,
+            MaterialMesh2dBundle {
+                mesh: meshes.add(shape::Circle::new(nest.radius).into()).into(),
+                material: material.clone(),
+                transform: Transform::from_translation(nest.center.extend(-0.5)),
+                ..default()
+            },
+        ));
+    }
+
+    let material = materials.add(ColorMaterial::from(Color::rgba(0.8, 0.0, 0.0, 0.4)));
+    for hazard in hazards {
+        commands.spawn((
+            Scenery,
+            MaterialMesh2dBundle {
+                mesh: meshes.add(shape::Circle::new(hazard.radius).into()).into(),
+                material: material.clone(),
+                transform: Transform::from_translation(Vec2::from_array(hazard.center).extend(-0.4)),
+                ..default()
+            },
+        ));
+    }
+}
+
 struct EntityFactories;
 
 impl EntityFactories {
     pub fn ant_factory(
         rng: &mut ResMut<GlobalEntropy<ChaCha8Rng>>,
-    ) -> (SpriteBundle, Ant, Inventory) {
-        let r = 200.0 * (rng.next_u32() as f32 / u32::MAX as f32);
-        let theta = 360f32.to_radians() * (rng.next_u32() as f32 / u32::MAX as f32);
+        nest: &Nest,
+        obstacles: &Obstacles,
+        capacity: f32,
+        traits: Traits,
+        caste: Caste,
+    ) -> (Ant, Inventory, Traits, Caste) {
+        // scatter around the nest, but never inside a wall
+        let position = (0..SPAWN_TRIES)
+            .map(|_| {
+                let r = 200.0 * (rng.next_u32() as f32 / u32::MAX as f32);
+                let theta = 360f32.to_radians() * (rng.next_u32() as f32 / u32::MAX as f32);
+                r * Vec2::from_angle(theta) + nest.center
+            })
+            .find(|&position| !obstacles.is_blocked(position))
+            .or_else(|| obstacles.nearest_free(nest.center, WALL_ESCAPE))
+            .unwrap_or(nest.center);
+        Self::ant_at(rng, position, capacity, traits, caste)
+    }
+
+    pub fn ant_at(
+        rng: &mut ResMut<GlobalEntropy<ChaCha8Rng>>,
+        position: Vec2,
+        capacity: f32,
+        traits: Traits,
+        caste: Caste,
+    ) -> (Ant, Inventory, Traits, Caste) {
         let heading = 360f32.to_radians() * (rng.next_u32() as f32 / u32::MAX as f32 - 0.5);
         let mut ant = Ant::new();
-        ant.position = r * Vec2::from_angle(theta) + NEST_CENTER;
+        ant.position = position;
 
         ant.velocity = ant.velocity.rotate(Vec2::from_angle(heading));
         ant.state = SignalKind::Exploring;
 
+        let inventory = Inventory::new(capacity * caste.profile().capacity);
+        Self::ant_bundle(ant, inventory, traits, caste)
+    }
+
+    pub fn ant_bundle(
+        ant: Ant,
+        inventory: Inventory,
+        traits: Traits,
+        caste: Caste,
+    ) -> (Ant, Inventory, Traits, Caste) {
+        // how the ant is drawn is left to whichever renderer is running
+        (ant, inventory, traits, caste)
+    }
+
+    pub fn predator_bundle(predator: Predator, predators: &Predators) -> (SpriteBundle, Predator) {
         (
             SpriteBundle {
                 sprite: Sprite {
-                    color: Color::RED,
-                    custom_size: Some(0.01 * Vec2::new(1920.0, 1080.0)),
+                    color: Color::BLACK,
+                    custom_size: Some(Vec2::splat(2.0 * predators.radius)),
                     ..default()
                 },
-                transform: Transform::from_translation(ant.position.extend(0f32))
-                    .with_rotation(Quat::from_rotation_arc_2d(Vec2::ZERO, ant.velocity)),
+                transform: Transform::from_translation(predator.position.extend(0.1)),
                 ..default()
             },
-            ant,
-            Inventory::new(2f32),
+            predator,
         )
     }
+
+    pub fn ant_size(caste: Caste) -> Vec2 {
+        let size = 0.01 * Vec2::new(1920.0, 1080.0);
+        // scouts are long and thin, soldiers are bulky
+        match caste {
+            Caste::Worker => size,
+            Caste::Scout => size * Vec2::new(1.3, 0.6),
+            Caste::Soldier => size * Vec2::new(1.2, 1.5),
+        }
+    }
+
+    pub fn ant_sprite(position: Vec2, heading: Vec2, state: SignalKind, caste: Caste) -> SpriteBundle {
+        SpriteBundle {
+            sprite: Sprite {
+                color: batch::state_color(state),
+                custom_size: Some(Self::ant_size(caste)),
+                ..default()
+            },
+            transform: Transform::from_translation(position.extend(0f32))
+                .with_rotation(Quat::from_rotation_arc_2d(Vec2::X, heading.normalize())),
+            ..default()
+        }
+    }
 }
 
 
 fn update_ants(
+    mut commands: Commands,
     mut signals: ResMut<Signals>,
-    mut query: Query<(&mut Transform, &mut Ant, &mut Inventory, &mut Sprite)>,
+    mut query: Query<(&mut Ant, &mut Inventory, &Traits, &Caste, Option<&mut Perception>)>,
     mut food: ResMut<Food>,
+    mut delivered: ResMut<FoodDelivered>,
+    mut nests: ResMut<Nests>,
+    obstacles: Res<Obstacles>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    config: Res<Config>,
     time: Res<Time>,
 ) {
     let dt = time.delta_seconds();
-    signals.update(&dt);
-    for (mut transform, mut ant, mut inventory, mut sprite) in &mut query {
-        let position = transform.translation.truncate();
-        if is_in_nest(&position) {
+    let params = &config.params;
+    signals.update(params.diffusion, params.evaporation, params.alarm_evaporation, &dt);
+    let mut births = vec![];
+    for (mut ant, mut inventory, traits, &caste, seen) in &mut query {
+        let position = ant.position;
+        if let Some(nest) = nests.nests.iter_mut().find(|nest| nest.contains(position)) {
             if inventory.is_full() {
-                inventory.dropoff(position, &mut food);
-            }    
+                let dropped = inventory.dropoff(position, &mut food);
+                delivered.total += dropped;
+                nest.stored += dropped;
+                // whoever tops up the store passes their traits on
+                if let Some(cost) = config.birth_cost {
+                    if nest.stored >= cost {
+                        nest.stored -= cost;
+                        births.push((*nest, traits.vary(config.mutation, &mut *rng)));
+                    }
+                }
+            }
         }
         if !inventory.is_full() {
             ant.state = SignalKind::Exploring;
-            sprite.color = Color::RED;
         }
-        
-        let old_heading = ant.velocity.normalize();
-        ant.update(&mut signals, &dt);
+
+        let perception = ant.update(&signals, params, &traits.for_caste(caste), &mut *rng, &dt);
+        if let Some(mut seen) = seen {
+            *seen = perception;
+        }
         let res = Vec2::new(RESOLUTION.0, RESOLUTION.1);
         ant.reach_around(Rect {
             min: -0.4 * res,
             max: 0.4 * res,
         });
-        if !f32::is_nan(old_heading.angle_between(ant.velocity.normalize())) {
-            transform.rotation = Quat::from_rotation_arc_2d(Vec2::X, ant.velocity);
+        if obstacles.is_blocked(ant.position) {
+            if !obstacles.is_blocked(position) {
+                // bounce off walls rather than walking through them
+                ant.position = position;
+                ant.velocity = -ant.velocity;
+            } else if let Some(free) = obstacles.nearest_free(position, WALL_ESCAPE) {
+                // walled in where it stood, e.g. painted over, so step out
+                ant.position = free;
+            }
+        }
+    }
+
+    for (nest, traits) in births {
+        // the colony decides what it needs, the parent only passes on its traits
+        let caste = config.castes.pick(rng.next_u32() as f32 / u32::MAX as f32);
+        commands.spawn(EntityFactories::ant_factory(&mut rng, &nest, &obstacles, params.capacity, traits, caste));
+    }
+}
+
+fn move_predators(
+    mut predators: Query<(&mut Transform, &mut Predator)>,
+    ants: Query<&Ant>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    let res = Vec2::new(RESOLUTION.0, RESOLUTION.1);
+    for (mut transform, mut predator) in &mut predators {
+        let here = predator.position;
+        let prey = ants
+            .iter()
+            .map(|ant| ant.position)
+            .min_by(|a, b| a.distance_squared(here).total_cmp(&b.distance_squared(here)));
+        predator.update(prey, &config.predators, &mut *rng, &dt);
+        predator.reach_around(Rect {
+            min: -0.4 * res,
+            max: 0.4 * res,
+        });
+        transform.translation = predator.position.extend(0.1);
+        transform.rotation = Quat::from_rotation_arc_2d(Vec2::X, predator.velocity);
+    }
+}
+
+fn threaten_ants(
+    mut commands: Commands,
+    mut signals: ResMut<Signals>,
+    mut casualties: ResMut<Casualties>,
+    mut query: Query<(Entity, &mut Ant, &Caste)>,
+    predators: Query<&Predator>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    let threats: Vec<Threat> = config
+        .hazards
+        .iter()
+        .map(Threat::from_hazard)
+        .chain(predators.iter().map(|predator| predator.threat(&config.predators)))
+        .collect();
+    if threats.is_empty() {
+        return;
+    }
+    for (entity, mut ant, caste) in &mut query {
+        let Some(threat) = threats.iter().find(|threat| threat.frightens(ant.position)) else {
+            continue;
+        };
+        let roll = rng.next_u32() as f64 / u32::MAX as f64;
+        if threat.reaches(ant.position) && roll < threat.kill_chance(dt) {
+            commands.entity(entity).despawn();
+            casualties.total += 1;
+            continue;
         }
+        // survivors turn tail, unless they're the sort that stands and fights
+        if caste.profile().alarm_response > 0.0 {
+            let away = (ant.position - threat.center).normalize_or_zero();
+            if away != Vec2::ZERO {
+                ant.velocity = away;
+            }
+        }
+        ant.raise_alarm(&mut signals, threat.center, config.params.alarm_deposit);
+    }
+}
+
+fn extend_trails(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Ant, Option<&mut Trail>)>,
+    config: Res<Config>,
+) {
+    for (entity, ant, trail) in &mut query {
+        match trail {
+            Some(mut trail) => trail.push(ant.position, config.trails.length),
+            None => {
+                let mut trail = Trail::default();
+                trail.push(ant.position, config.trails.length);
+                // the ant may die later this tick, before the insert lands
+                commands.entity(entity).try_insert(trail);
+            }
+        }
+    }
+}
+
+fn draw_trails(query: Query<(&Ant, &Trail)>, mut gizmos: Gizmos) {
+    // longer than any ant walks in a tick, so it must be a wrap-around
+    const MAX_STEP: f32 = 100.0;
+
+    for (ant, trail) in &query {
+        let color = batch::state_color(ant.state);
+        let count = trail.points.len() as f32;
+        for (i, (&from, &to)) in trail.points.iter().zip(trail.points.iter().skip(1)).enumerate() {
+            if from.distance(to) > MAX_STEP {
+                continue;
+            }
+            gizmos.line_2d(from, to, color.with_a(0.6 * (i + 1) as f32 / count));
+        }
+    }
+}
+
+fn tread_footfall(
+    mut footfall: ResMut<Footfall>,
+    ants: Query<&Ant>,
+    config: Res<Config>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+    footfall.steps.update(0.0, config.trails.decay, &dt);
+    for ant in &ants {
+        footfall.steps.acc_cell_value(1.0, ant.position);
+    }
+}
+
+fn dress_ants(mut commands: Commands, query: Query<(Entity, &Ant, &Caste), Added<Ant>>) {
+    for (entity, ant, &caste) in &query {
+        // a snapshot loaded this frame may have despawned it already
+        commands
+            .entity(entity)
+            .try_insert(EntityFactories::ant_sprite(ant.position, ant.velocity, ant.state, caste));
+    }
+}
+
+fn move_ant_sprites(mut query: Query<(&Ant, &mut Transform, &mut Sprite)>) {
+    for (ant, mut transform, mut sprite) in &mut query {
         transform.translation = ant.position.extend(0.0);
+        if let Some(heading) = ant.velocity.try_normalize() {
+            transform.rotation = Quat::from_rotation_arc_2d(Vec2::X, heading);
+        }
+        sprite.color = batch::state_color(ant.state);
     }
 }
 
-fn update_cells(
-    mut query: Query<(&mut Cell, &mut Sprite)>,
+fn draw_ant_batch(
+    batch: Query<&Mesh2dHandle, With<AntBatch>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    ants: Query<(&Ant, &Caste)>,
+) {
+    let Ok(handle) = batch.get_single() else {
+        return;
+    };
+    let Some(mesh) = meshes.get_mut(&handle.0) else {
+        return;
+    };
+    let frames: Vec<AntFrame> = ants.iter().map(|(ant, &caste)| AntFrame::capture(ant, caste)).collect();
+    batch::write_mesh(mesh, &frames);
+}
+
+fn update_field_textures(
+    query: Query<(&FieldLayer, &Handle<Image>)>,
+    mut images: ResMut<Assets<Image>>,
     food: Res<Food>,
+    obstacles: Res<Obstacles>,
+    footfall: Option<Res<Footfall>>,
 ) {
-    for (mut cell, mut sprite) in &mut query {
-        cell.read_from(&food.amount);
-        sprite.color.set_a(cell.val/10f32);
+    // footfall never stops piling up, so ease into full brightness
+    const FOOTFALL_SATURATION: f32 = 20.0;
+
+    for (layer, handle) in &query {
+        // get_mut alone marks the image for upload, so only touch changed ones
+        let changed = match layer {
+            FieldLayer::Food => food.is_changed(),
+            FieldLayer::Walls => obstacles.is_changed(),
+            FieldLayer::Footfall => footfall.as_ref().is_some_and(|footfall| footfall.is_changed()),
+        };
+        if !changed {
+            continue;
+        }
+        let Some(image) = images.get_mut(handle) else {
+            continue;
+        };
+        match layer {
+            FieldLayer::Food => texture::field_pixels(
+                &food.amount,
+                Color::rgb(0.7, 0.7, 0.0),
+                |value| value / MAX_FOOD_HEIGHT,
+                &mut image.data,
+            ),
+            FieldLayer::Walls => texture::field_pixels(
+                &obstacles.walls,
+                Color::GRAY,
+                |value| if value > 0.5 { 1.0 } else { 0.0 },
+                &mut image.data,
+            ),
+            FieldLayer::Footfall => {
+                if let Some(footfall) = &footfall {
+                    texture::field_pixels(
+                        &footfall.steps,
+                        Color::WHITE,
+                        |value| 1.0 - (-value / FOOTFALL_SATURATION).exp(),
+                        &mut image.data,
+                    );
+                }
+            }
+        }
     }
 }
 
 fn leave_signals(
     mut signals: ResMut<Signals>,
-    mut query: Query<&mut Ant, With<Transform>>,
+    mut query: Query<&mut Ant>,
+    config: Res<Config>,
 ) {
     for ant in &mut query {
-        ant.leave_signal(&mut signals);
+        ant.leave_signal(&mut signals, config.params.deposit);
     }
 }
 
 fn take_food(
-    mut query: Query<(&Transform, &mut Ant, &mut Inventory, &mut Sprite)>,
+    mut query: Query<(&mut Ant, &mut Inventory)>,
     mut food: ResMut<Food>,
+    nests: Res<Nests>,
     time: Res<Time>,
 ) {
     food.update(&time.delta_seconds());
-    for (transform, mut ant, mut inventory, mut sprite) in &mut query {
+    for (mut ant, mut inventory) in &mut query {
         if ant.state == SignalKind::Exploring {
-            let pos = transform.translation.truncate();
-            if !is_in_nest(&pos) {
+            let pos = ant.position;
+            if !nests.contains(pos) {
                 inventory.fill_from(pos, &mut food);
             }
             if inventory.is_full() {
                 ant.state = SignalKind::Retrieving;
-                sprite.color = Color::GREEN;
             }
         }
     }
 }
 
-fn put_food(
+fn track_cursor(
+    mut cursor: ResMut<Cursor>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform)>,
+) {
+    let (Ok(window), Ok((camera, transform))) = (windows.get_single(), camera.get_single()) else {
+        cursor.world = None;
+        return;
+    };
+    cursor.world = window
+        .cursor_position()
+        .and_then(|position| camera.viewport_to_world_2d(transform, position));
+}
+
+fn select_tool(keys: Res<Input<KeyCode>>, mut toolbox: ResMut<Toolbox>) {
+    for (key, tool) in Tool::KEYS {
+        if keys.just_pressed(key) && toolbox.tool != tool {
+            toolbox.tool = tool;
+            info!("Tool {tool:?}");
+        }
+    }
+    if keys.just_pressed(KeyCode::Equals) {
+        toolbox.resize(1.25);
+        info!("Brush radius {}", toolbox.radius);
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        toolbox.resize(0.8);
+        info!("Brush radius {}", toolbox.radius);
+    }
+}
+
+fn paint_fields(
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: Res<Cursor>,
+    mut toolbox: ResMut<Toolbox>,
     mut food: ResMut<Food>,
+    mut obstacles: ResMut<Obstacles>,
+    mut signals: ResMut<Signals>,
+    config: Res<Config>,
+) {
+    let Some(position) = cursor.world.filter(|_| buttons.pressed(MouseButton::Left)) else {
+        toolbox.last = None;
+        return;
+    };
+    let erase = tools::erasing(&keys);
+    let radius = toolbox.radius;
+    match toolbox.tool {
+        Tool::Food => food.amount.paint(position, radius, if erase { 0.0 } else { MAX_FOOD_HEIGHT }),
+        Tool::Walls => {
+            obstacles.walls.paint(position, radius, if erase { 0.0 } else { 1.0 });
+        }
+        Tool::Pheromone if erase => {
+            signals.exploring.paint(position, radius, Vec2::ZERO);
+            signals.retrieving.paint(position, radius, Vec2::ZERO);
+            signals.alarm.paint(position, radius, Vec2::ZERO);
+        }
+        Tool::Pheromone => {
+            // lay a trail both ways along the stroke, as if ants had walked it
+            // out from where it starts and brought food back
+            let direction = toolbox.last.map_or(Vec2::ZERO, |last| (position - last).normalize_or_zero());
+            let strength = config.params.deposit;
+            signals.exploring.acc_paint(position, radius, strength * direction);
+            signals.retrieving.acc_paint(position, radius, -strength * direction);
+        }
+        Tool::Nests | Tool::Ants | Tool::Inspect => (),
+    }
+    toolbox.last = Some(position);
+}
+
+fn edit_nests(
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: Res<Cursor>,
+    mut toolbox: ResMut<Toolbox>,
+    mut nests: ResMut<Nests>,
+) {
+    if buttons.just_released(MouseButton::Left) {
+        toolbox.grabbed = None;
+    }
+    if toolbox.tool != Tool::Nests {
+        return;
+    }
+    let Some(position) = cursor.world else {
+        return;
+    };
+
+    if buttons.just_pressed(MouseButton::Left) {
+        let under_cursor = nests.nests.iter().position(|nest| nest.contains(position));
+        match (under_cursor, tools::erasing(&keys)) {
+            (Some(i), true) => {
+                nests.nests.remove(i);
+            }
+            (Some(i), false) => toolbox.grabbed = Some(i),
+            (None, false) => nests.nests.push(Nest {
+                center: position,
+                radius: NEST_RADIUS_SQ.sqrt(),
+                stored: 0.0,
+            }),
+            (None, true) => return,
+        }
+        toolbox.scenery_dirty = true;
+    } else if let Some(i) = toolbox.grabbed {
+        if let Some(nest) = nests.nests.get_mut(i) {
+            nest.center = position;
+            toolbox.scenery_dirty = true;
+        }
+    }
+}
+
+fn edit_ants(
+    mut commands: Commands,
+    buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: Res<Cursor>,
+    toolbox: Res<Toolbox>,
+    ants: Query<(Entity, &Ant)>,
+    obstacles: Res<Obstacles>,
+    mut rng: ResMut<GlobalEntropy<ChaCha8Rng>>,
+    config: Res<Config>,
+) {
+    const ANTS_PER_FRAME: usize = 5;
+
+    if toolbox.tool != Tool::Ants || !buttons.pressed(MouseButton::Left) {
+        return;
+    }
+    let Some(position) = cursor.world else {
+        return;
+    };
+    let radius = toolbox.radius;
+
+    if tools::erasing(&keys) {
+        for (entity, ant) in &ants {
+            if ant.position.distance_squared(position) < radius * radius {
+                commands.entity(entity).despawn();
+            }
+        }
+        return;
+    }
+    let traits = Traits::from_params(&config.params);
+    for _ in 0..ANTS_PER_FRAME {
+        let r = radius * (rng.next_u32() as f32 / u32::MAX as f32).sqrt();
+        let theta = 360f32.to_radians() * (rng.next_u32() as f32 / u32::MAX as f32);
+        let spot = position + r * Vec2::from_angle(theta);
+        if obstacles.is_blocked(spot) {
+            continue;
+        }
+        let traits = traits.vary(config.trait_spread, &mut *rng);
+        let caste = config.castes.pick(rng.next_u32() as f32 / u32::MAX as f32);
+        commands.spawn(EntityFactories::ant_at(
+            &mut rng,
+            spot,
+            config.params.capacity,
+            traits,
+            caste,
+        ));
+    }
+}
+
+fn redraw_scenery(
+    mut commands: Commands,
+    mut toolbox: ResMut<Toolbox>,
+    nests: Res<Nests>,
+    scenery: Query<Entity, With<Scenery>>,
+    config: Res<Config>,
+    meshes: ResMut<Assets<Mesh>>,
+    materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !toolbox.scenery_dirty {
+        return;
+    }
+    toolbox.scenery_dirty = false;
+    for entity in &scenery {
+        commands.entity(entity).despawn();
+    }
+    spawn_scenery(&mut commands, &nests, &config.hazards, meshes, materials);
+}
+
+fn toggle_hud(keys: Res<Input<KeyCode>>, mut query: Query<&mut Visibility, With<HudRoot>>) {
+    if !keys.just_pressed(KeyCode::H) {
+        return;
+    }
+    for mut visibility in &mut query {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+fn update_hud(
+    mut hud: ResMut<Hud>,
+    mut text: Query<&mut Text, With<HudText>>,
+    mut bars: Query<(&SparkBar, &mut Style)>,
+    root: Query<&Visibility, With<HudRoot>>,
+    diagnostics: Res<DiagnosticsStore>,
+    virtual_time: Res<Time<Virtual>>,
+    fixed_time: Res<Time<Fixed>>,
+    tick: Res<Tick>,
+    food: Res<Food>,
+    delivered: Res<FoodDelivered>,
+    casualties: Res<Casualties>,
+    signals: Res<Signals>,
+    config: Res<Config>,
+    ants: Query<(&Ant, &Traits)>,
+) {
+    let metrics = Metrics::measure(tick.0, &food, &delivered, &casualties, &signals, ants.iter());
+    // keep sampling while hidden so the sparklines are full when shown again
+    hud.sample(&metrics, fixed_time.timestep().as_secs_f32());
+    if root.iter().all(|visibility| *visibility == Visibility::Hidden) {
+        return;
+    }
+
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed());
+    for mut text in &mut text {
+        text.sections[0].value = hud::text(
+            &metrics,
+            hud.rate,
+            fps,
+            virtual_time.relative_speed(),
+            virtual_time.is_paused(),
+            &config.params,
+        );
+    }
+    for (bar, mut style) in &mut bars {
+        style.height = Val::Percent(100.0 * hud.bar(bar.line, bar.index));
+    }
+}
+
+fn pick_ant(
+    mut commands: Commands,
     buttons: Res<Input<MouseButton>>,
-    q_windows: Query<&Window, With<PrimaryWindow>>,
+    keys: Res<Input<KeyCode>>,
+    cursor: Res<Cursor>,
+    toolbox: Res<Toolbox>,
+    mut inspector: ResMut<Inspector>,
+    ants: Query<(Entity, &Ant)>,
+    inspected: Query<Entity, With<Inspected>>,
 ) {
-    if buttons.pressed(MouseButton::Left) {
-        if let Some(mouse_pos) = q_windows.single().cursor_position() {
-            
-            let world_pos = Vec2::new(
-                mouse_pos.x - RESOLUTION.0/2., 
-                RESOLUTION.1/2. - mouse_pos.y,
-            ) - Vec2::new(-140., 90.);
+    if keys.just_pressed(KeyCode::C) {
+        inspector.follow = !inspector.follow;
+    }
+    let picked = match cursor.world {
+        Some(position) if toolbox.tool == Tool::Inspect && buttons.just_pressed(MouseButton::Left) => ants
+            .iter()
+            .map(|(entity, ant)| (entity, ant.position.distance_squared(position)))
+            .filter(|&(_, distance_sq)| distance_sq < toolbox.radius * toolbox.radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(entity, _)| entity),
+        _ => None,
+    };
+    if picked.is_none() && !keys.just_pressed(KeyCode::Escape) {
+        return;
+    }
 
-            food.put(Rect::from_center_size(world_pos, Vec2::splat(50f32)), 10f32);
+    for entity in &inspected {
+        commands.entity(entity).remove::<(Inspected, Perception)>();
+    }
+    if let Some(entity) = picked {
+        commands.entity(entity).try_insert((Inspected, Perception::default()));
+    }
+}
+
+fn follow_inspected(
+    inspector: Res<Inspector>,
+    inspected: Query<&Ant, With<Inspected>>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+) {
+    if !inspector.follow {
+        return;
+    }
+    let (Ok(ant), Ok(mut transform)) = (inspected.get_single(), camera.get_single_mut()) else {
+        return;
+    };
+    transform.translation.x = ant.position.x;
+    transform.translation.y = ant.position.y;
+}
+
+fn show_inspector(
+    inspector: Res<Inspector>,
+    inspected: Query<(&Ant, &Inventory, &Traits, &Caste, &Perception), With<Inspected>>,
+    mut panel: Query<&mut Visibility, With<InspectorPanel>>,
+    mut text: Query<&mut Text, With<InspectorText>>,
+    mut gizmos: Gizmos,
+) {
+    let selected = inspected.get_single().ok();
+    for mut visibility in &mut panel {
+        *visibility = match selected {
+            Some(_) => Visibility::Inherited,
+            None => Visibility::Hidden,
+        };
+    }
+    let Some((ant, inventory, traits, &caste, perception)) = selected else {
+        return;
+    };
+    gizmos.circle_2d(ant.position, 2.0 * EntityFactories::ant_size(caste).max_element(), Color::CYAN);
+    for mut text in &mut text {
+        text.sections[0].value = inspect::text(ant, inventory, traits, caste, perception, inspector.follow);
+    }
+}
+
+fn toggle_debug_gizmos(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut debug: ResMut<DebugGizmos>,
+    untagged: Query<Entity, (With<Ant>, Without<Perception>)>,
+    tagged: Query<(Entity, Has<Inspected>), With<Debugged>>,
+    config: Res<Config>,
+) {
+    if keys.just_pressed(KeyCode::G) {
+        debug.enabled = !debug.enabled;
+        if !debug.enabled {
+            for (entity, inspected) in &tagged {
+                // the inspector still wants the perception of its own ant
+                match inspected {
+                    true => commands.entity(entity).remove::<Debugged>(),
+                    false => commands.entity(entity).remove::<(Debugged, Perception)>(),
+                };
+            }
         }
     }
+    if !debug.enabled {
+        return;
+    }
+    // also picks up ants born since, or ones the inspector let go of
+    for entity in &untagged {
+        if entity.index() % config.debug_every.max(1) == 0 {
+            commands.entity(entity).try_insert((Debugged, Perception::default()));
+        }
+    }
+}
+
+fn draw_debug_gizmos(
+    debug: Res<DebugGizmos>,
+    query: Query<(&Ant, &Perception), With<Debugged>>,
+    mut gizmos: Gizmos,
+) {
+    const LENGTH: f32 = 20.0;
+
+    if !debug.enabled {
+        return;
+    }
+    let scaled = |value: Vec2| LENGTH * value.normalize_or_zero() * (value.length() / (value.length() + 1.0));
+    for (ant, perception) in &query {
+        let heading = ant.velocity.normalize_or_zero();
+        let wander = Vec2::from_angle(perception.terms[0].2).rotate(heading);
+        gizmos.line_2d(ant.position, ant.position + LENGTH * heading, Color::WHITE);
+        gizmos.line_2d(ant.position, ant.position + 0.5 * LENGTH * wander, Color::FUCHSIA);
+        gizmos.line_2d(ant.position, ant.position + scaled(perception.trail_direction), Color::YELLOW);
+        gizmos.line_2d(ant.position, ant.position + scaled(perception.alarm_direction), Color::BLUE);
+        gizmos.line_2d(perception.sensor, perception.sensor + scaled(perception.exploring), Color::RED);
+        gizmos.line_2d(perception.sensor, perception.sensor + scaled(perception.retrieving), Color::GREEN);
+        gizmos.circle_2d(perception.sensor, 2.0, Color::CYAN);
+    }
 }
 
+fn control_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<Overlay>) {
+    if keys.just_pressed(KeyCode::O) {
+        overlay.mode = overlay.mode.next();
+        info!("Overlay {:?}", overlay.mode);
+    }
+    if keys.just_pressed(KeyCode::PageUp) {
+        overlay.scale.rescale(1.5);
+        info!("Overlay saturates at {}", overlay.scale.saturation);
+    }
+    if keys.just_pressed(KeyCode::PageDown) {
+        overlay.scale.rescale(1.0 / 1.5);
+        info!("Overlay saturates at {}", overlay.scale.saturation);
+    }
+    if keys.just_pressed(KeyCode::L) {
+        overlay.scale.log = !overlay.scale.log;
+        info!("Overlay log scale {}", overlay.scale.log);
+    }
+}
+
+fn show_overlay(
+    overlay: Res<Overlay>,
+    signals: Res<Signals>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites: Query<&mut Visibility, With<OverlaySprite>>,
+    mut gizmos: Gizmos,
+) {
+    const ARROW_STRIDE: usize = 3;
 
+    for mut visibility in &mut sprites {
+        *visibility = match overlay.mode.heatmap() {
+            true => Visibility::Visible,
+            false => Visibility::Hidden,
+        };
+    }
+    if overlay.mode.heatmap() {
+        if let Some(image) = images.get_mut(&overlay.image) {
+            overlay::heatmap_pixels(&signals, &overlay.scale, &mut image.data);
+        }
+    }
+    if !overlay.mode.arrows() {
+        return;
+    }
+
+    let spacing = ARROW_STRIDE as f32 * signals.exploring.get_lattice().min_element();
+    let fields = [
+        (overlay::arrows(&signals.exploring, ARROW_STRIDE), Color::RED),
+        (overlay::arrows(&signals.retrieving, ARROW_STRIDE), Color::GREEN),
+        (overlay::arrows(&signals.alarm, ARROW_STRIDE), Color::BLUE),
+    ];
+    for (arrows, color) in fields {
+        for (center, value) in arrows {
+            let length = spacing * overlay.scale.map(value.length());
+            if length < 1.0 {
+                continue;
+            }
+            let direction = value.normalize();
+            let tip = center + length * direction;
+            gizmos.line_2d(center, tip, color);
+            for barb in [Vec2::from_angle(2.6), Vec2::from_angle(-2.6)] {
+                gizmos.line_2d(tip, tip + 0.3 * length * barb.rotate(direction), color);
+            }
+        }
+    }
+}
+
+fn zoom_camera(
+    mut wheel: EventReader<MouseWheel>,
+    mut camera: Query<&mut OrthographicProjection, With<Camera>>,
+) {
+    let mut projection = camera.single_mut();
+    for event in wheel.read() {
+        let notches = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 100.0,
+        };
+        projection.scale = (projection.scale * 1.1f32.powf(-notches)).clamp(ZOOMS.0, ZOOMS.1);
+    }
+}
+
+// Keeps the world point grabbed with the right button under the cursor, which
+// holds whatever the zoom or the display scaling.
+fn pan_camera(
+    buttons: Res<Input<MouseButton>>,
+    cursor: Res<Cursor>,
+    mut grabbed: Local<Option<Vec2>>,
+    mut camera: Query<&mut Transform, With<Camera>>,
+) {
+    if !buttons.pressed(MouseButton::Right) {
+        *grabbed = None;
+        return;
+    }
+    let Some(under) = cursor.world else {
+        return;
+    };
+    let grab = *grabbed.get_or_insert(under);
+    camera.single_mut().translation += (grab - under).extend(0.0);
+}
+
+fn fit_camera(
+    keys: Res<Input<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<Camera>>,
+) {
+    if !keys.just_pressed(KeyCode::F) {
+        return;
+    }
+    let window = windows.single();
+    let (mut transform, mut projection) = camera.single_mut();
+    transform.translation.x = 0.0;
+    transform.translation.y = 0.0;
+    projection.scale = (RESOLUTION.0 / window.width())
+        .max(RESOLUTION.1 / window.height())
+        .clamp(ZOOMS.0, ZOOMS.1);
+}
+
+fn age_ants(
+    mut commands: Commands,
+    query: Query<(Entity, &Ant)>,
+    config: Res<Config>,
+) {
+    let Some(lifespan) = config.lifespan else {
+        return;
+    };
+    for (entity, ant) in &query {
+        if ant.age > lifespan {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn count_ticks(mut tick: ResMut<Tick>) {
+    tick.0 += 1;
+}
+
+// Speeding up only changes how many fixed ticks run per frame, never their
+// length, so the ants behave exactly as they would at 1x.
+fn control_time(
+    keys: Res<Input<KeyCode>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut steps: ResMut<PendingSteps>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        if virtual_time.is_paused() {
+            virtual_time.unpause();
+            info!("Resumed");
+        } else {
+            virtual_time.pause();
+            info!("Paused");
+        }
+    }
+    if keys.just_pressed(KeyCode::Period) && virtual_time.is_paused() {
+        steps.0 += 1;
+    }
+
+    let speed = virtual_time.relative_speed();
+    let new_speed = if keys.just_pressed(KeyCode::BracketRight) {
+        (speed * 2.0).min(SPEEDS.1)
+    } else if keys.just_pressed(KeyCode::BracketLeft) {
+        (speed * 0.5).max(SPEEDS.0)
+    } else {
+        speed
+    };
+    if new_speed != speed {
+        virtual_time.set_relative_speed(new_speed);
+        info!("Speed {new_speed}x");
+    }
+}
+
+// Runs one fixed tick by hand, with `Time` showing a fixed timestep the way
+// the fixed loop would, since paused virtual time never feeds that loop.
+fn step_paused(world: &mut World) {
+    if world.resource::<PendingSteps>().0 == 0 {
+        return;
+    }
+    world.resource_mut::<PendingSteps>().0 -= 1;
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    world.resource_mut::<Time<Fixed>>().advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    let _ = world.try_run_schedule(FixedUpdate);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+fn capture_window(
+    keys: Res<Input<KeyCode>>,
+    mut export: ResMut<FrameExport>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    window: Query<Entity, With<PrimaryWindow>>,
+    tick: Res<Tick>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+    export.count += 1;
+    let mut paths = vec![];
+    if keys.just_pressed(KeyCode::F12) {
+        paths.push(export.images.screenshot.clone().unwrap_or_else(|| {
+            PathBuf::from(format!("screenshot_{:06}.png", tick.0))
+        }));
+    }
+    paths.extend(export.frame_path(export.count));
+    if paths.is_empty() {
+        return;
+    }
+    // a window takes one screenshot request per frame, so every path shares it
+    let request = screenshots.take_screenshot(window, move |image| match image.try_into_dynamic() {
+        Ok(image) => {
+            // alpha holds no coverage here, so leave it out like bevy does
+            let image = image.to_rgb8();
+            for path in &paths {
+                match image.save(path) {
+                    Ok(()) => info!("Saved {path:?}"),
+                    Err(err) => error!("Could not save {path:?}: {err}"),
+                }
+            }
+        }
+        Err(err) => error!("Could not read back the window: {err}"),
+    });
+    if let Err(err) = request {
+        error!("Could not capture the window: {err}");
+    }
+}
+
+// Headless runs have no window to grab, so frames are drawn on the CPU.
+fn capture_raster(
+    export: Res<FrameExport>,
+    mut failure: ResMut<OutputFailure>,
+    tick: Res<Tick>,
+    food: Res<Food>,
+    obstacles: Res<Obstacles>,
+    nests: Res<Nests>,
+    signals: Res<Signals>,
+    config: Res<Config>,
+    ants: Query<(&Ant, &Caste)>,
+) {
+    let Some(path) = export.frame_path(tick.0) else {
+        return;
+    };
+    let frames: Vec<AntFrame> = ants.iter().map(|(ant, &caste)| AntFrame::capture(ant, caste)).collect();
+    let signals = export.images.pheromones.then_some(&*signals);
+    if let Err(err) = raster_frame(&food, &obstacles, &nests, &config, signals, &frames).save(&path) {
+        failure.report(format!("Could not save frame to {path:?}: {err}"));
+    }
+}
+
+// Everything a snapshot is taken from and put back into.
+#[derive(SystemParam)]
+struct SimState<'w, 's> {
+    commands: Commands<'w, 's>,
+    tick: ResMut<'w, Tick>,
+    rng: ResMut<'w, GlobalEntropy<ChaCha8Rng>>,
+    delivered: ResMut<'w, FoodDelivered>,
+    casualties: ResMut<'w, Casualties>,
+    food: ResMut<'w, Food>,
+    obstacles: ResMut<'w, Obstacles>,
+    signals: ResMut<'w, Signals>,
+    nests: ResMut<'w, Nests>,
+    ants: Query<'w, 's, (Entity, &'static Ant, &'static Inventory, &'static Traits, &'static Caste)>,
+    predators: Query<'w, 's, (Entity, &'static Predator)>,
+    config: Res<'w, Config>,
+}
+
+impl SimState<'_, '_> {
+    fn capture(&self) -> Snapshot {
+        Snapshot::capture(
+            self.tick.0,
+            &self.rng,
+            (&self.delivered, &self.casualties),
+            (&self.food, &self.obstacles, &self.signals, &self.nests),
+            self.ants.iter().map(|(_, ant, inventory, traits, caste)| (ant, inventory, traits, caste)),
+            self.predators.iter().map(|(_, predator)| predator),
+        )
+    }
+
+    // Swaps the running colony for the saved one, or leaves it alone if the
+    // snapshot doesn't fit this world.
+    fn restore(&mut self, snapshot: Snapshot) -> io::Result<()> {
+        let (saved_tick, saved_rng) = (snapshot.tick, snapshot.rng.clone());
+        let (saved_delivered, saved_deaths) = (snapshot.delivered, snapshot.deaths);
+        let (saved_ants, saved_predators) =
+            snapshot.restore_world(&mut self.food, &mut self.obstacles, &mut self.signals, &mut self.nests)?;
+        self.tick.0 = saved_tick;
+        *self.rng = saved_rng;
+        self.delivered.total = saved_delivered;
+        self.casualties.total = saved_deaths;
+
+        for (entity, ..) in &self.ants {
+            self.commands.entity(entity).despawn();
+        }
+        let default_traits = Traits::from_params(&self.config.params);
+        for saved in &saved_ants {
+            let (ant, inventory, traits, caste) = saved.restore(default_traits);
+            self.commands.spawn(EntityFactories::ant_bundle(ant, inventory, traits, caste));
+        }
+        // older snapshots know nothing of predators, so those running carry on
+        if let Some(saved_predators) = saved_predators {
+            for (entity, _) in &self.predators {
+                self.commands.entity(entity).despawn();
+            }
+            for saved in &saved_predators {
+                self.commands.spawn(EntityFactories::predator_bundle(saved.restore(), &self.config.predators));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn save_snapshot(keys: Res<Input<KeyCode>>, file: Res<SnapshotFile>, state: SimState) {
+    if !keys.just_pressed(KeyCode::F5) {
+        return;
+    }
+    let path = &file.0;
+    match state.capture().save(path) {
+        Ok(()) => info!("Saved tick {} to {path:?}", state.tick.0),
+        Err(err) => error!("Could not save snapshot to {path:?}: {err}"),
+    }
+}
+
+fn load_snapshot(
+    keys: Res<Input<KeyCode>>,
+    file: Res<SnapshotFile>,
+    mut state: SimState,
+    scenery: Query<Entity, With<Scenery>>,
+    meshes: ResMut<Assets<Mesh>>,
+    materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let path = &file.0;
+    let snapshot = match Snapshot::load(path) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            error!("Could not load snapshot from {path:?}: {err}");
+            return;
+        }
+    };
+    if let Err(err) = state.restore(snapshot) {
+        error!("Could not restore snapshot from {path:?}: {err}");
+        return;
+    }
+
+    for entity in &scenery {
+        state.commands.entity(entity).despawn();
+    }
+    spawn_scenery(&mut state.commands, &state.nests, &state.config.hazards, meshes, materials);
+    info!("Loaded tick {} from {path:?}", state.tick.0);
+}
+
+fn resume_snapshot(resume: Res<Resume>, mut state: SimState) {
+    let path = &resume.0;
+    let snapshot = Snapshot::load(path)
+        .unwrap_or_else(|err| panic!("Could not load snapshot from {path:?}: {err}"));
+    state
+        .restore(snapshot)
+        .unwrap_or_else(|err| panic!("Could not restore snapshot from {path:?}: {err}"));
+}
+
+fn record_ants(
+    mut recording: ResMut<Recording>,
+    mut failure: ResMut<OutputFailure>,
+    tick: Res<Tick>,
+    query: Query<(Entity, &Ant, &Caste)>,
+) {
+    let mut ants: Vec<(Entity, &Ant, &Caste)> = query.iter().collect();
+    // Keep slots stable between frames so the deltas stay small.
+    ants.sort_by_key(|(entity, _, _)| *entity);
+    let frames: Vec<AntFrame> = ants
+        .iter()
+        .map(|&(_, ant, &caste)| AntFrame::capture(ant, caste))
+        .collect();
+
+    if let Err(err) = recording.0.write_frame(tick.0, &frames) {
+        failure.report(format!("Could not record tick {}: {err}", tick.0));
+    }
+}
+
+fn advance_replay(mut replay: ResMut<Replay>) {
+    if !replay.playing {
+        return;
+    }
+    if replay.frame + 1 < replay.player.len() {
+        replay.frame += 1;
+    } else {
+        replay.playing = false;
+    }
+}
+
+fn scrub_replay(keys: Res<Input<KeyCode>>, mut replay: ResMut<Replay>) {
+    if keys.just_pressed(KeyCode::Space) {
+        replay.playing = !replay.playing;
+    }
+    if keys.just_pressed(KeyCode::Home) {
+        replay.frame = 0;
+    }
+    if keys.pressed(KeyCode::Left) {
+        replay.playing = false;
+        replay.frame = replay.frame.saturating_sub(1);
+    }
+    if keys.pressed(KeyCode::Right) {
+        replay.playing = false;
+        replay.frame = (replay.frame + 1).min(replay.player.len().saturating_sub(1));
+    }
+}
+
+fn show_replay(
+    mut commands: Commands,
+    mut replay: ResMut<Replay>,
+    mut tick: ResMut<Tick>,
+    mut query: Query<(&mut Transform, &mut Sprite)>,
+) {
+    if !replay.is_changed() {
+        return;
+    }
+    let frame = replay.frame;
+    let Some(ants) = replay.player.frame(frame) else {
+        return;
+    };
+    tick.0 = replay.player.tick(frame).unwrap_or(tick.0);
+
+    while replay.sprites.len() > ants.len() {
+        if let Some(entity) = replay.sprites.pop() {
+            commands.entity(entity).despawn();
+        }
+    }
+    for (i, ant) in ants.iter().enumerate() {
+        let heading = Vec2::from_angle(ant.heading);
+        if let Some(&entity) = replay.sprites.get(i) {
+            if let Ok((mut transform, mut sprite)) = query.get_mut(entity) {
+                transform.translation = ant.position.extend(0.0);
+                transform.rotation = Quat::from_rotation_arc_2d(Vec2::X, heading);
+                sprite.color = batch::state_color(ant.state);
+                sprite.custom_size = Some(EntityFactories::ant_size(ant.caste));
+            }
+            continue;
+        }
+        let entity = commands
+            .spawn(EntityFactories::ant_sprite(ant.position, heading, ant.state, ant.caste))
+            .id();
+        replay.sprites.push(entity);
+    }
+}
+
+fn write_metrics(
+    mut log: ResMut<MetricsLog>,
+    mut failure: ResMut<OutputFailure>,
+    tick: Res<Tick>,
+    food: Res<Food>,
+    delivered: Res<FoodDelivered>,
+    casualties: Res<Casualties>,
+    signals: Res<Signals>,
+    query: Query<(&Ant, &Traits)>,
+) {
+    let metrics = Metrics::measure(tick.0, &food, &delivered, &casualties, &signals, query.iter());
+    if let Err(err) = log.write(&metrics) {
+        failure.report(format!("Could not write metrics for tick {}: {err}", tick.0));
+    }
+}
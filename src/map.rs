@@ -0,0 +1,111 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use bevy::prelude::*;
+use image::imageops::FilterType;
+use image::ImageResult;
+
+use crate::field::{Food, Obstacles};
+use crate::nest::{Nest, Nests};
+
+// Greyscale images only carry food. Colour images are read channel by channel:
+// red paints walls, green paints food and blue paints nests. What counts is
+// the pixels, not the file format, since editors often save grey as RGB.
+pub struct WorldMap {
+    pub food: Food,
+    pub obstacles: Obstacles,
+    pub nests: Option<Nests>,
+}
+
+impl WorldMap {
+    const THRESHOLD: f32 = 0.5;
+
+    pub fn load(path: &Path, lattice: Vec2, size: Vec2, max_food: f32) -> ImageResult<Self> {
+        let image = image::open(path)?;
+        let has_color = image.to_rgb8().pixels().any(|&image::Rgb([r, g, b])| r != g || g != b);
+
+        let mut food = Food::new(lattice, size);
+        let mut obstacles = Obstacles::new(lattice, size);
+        let dimensions = food.amount.dimensions;
+
+        // Resample onto the lattice, one pixel per cell.
+        let pixels = image
+            .resize_exact(dimensions.w() as u32, dimensions.h() as u32, FilterType::Triangle)
+            .to_rgba32f();
+
+        let mut nest_cells = vec![false; dimensions.linear_max()];
+        for (x_idx, y_idx) in (0..dimensions.linear_max()).filter_map(|i| dimensions.to_grid(i)) {
+            // Images are stored top row first but the lattice starts at the bottom.
+            let [r, g, b, _] = pixels.get_pixel(x_idx as u32, (dimensions.h() - 1 - y_idx) as u32).0;
+            if !has_color {
+                food.amount.set_cell_value_at_lattice_idx(r * max_food, (x_idx, y_idx));
+                continue;
+            }
+
+            food.amount.set_cell_value_at_lattice_idx(g * max_food, (x_idx, y_idx));
+            if r > Self::THRESHOLD {
+                obstacles.walls.set_cell_value_at_lattice_idx(1.0, (x_idx, y_idx));
+            }
+            if let Some(linear) = dimensions.to_linear((x_idx, y_idx)) {
+                nest_cells[linear] = b > Self::THRESHOLD;
+            }
+        }
+
+        let nests = Self::find_nests(&food, &mut nest_cells);
+        Ok(Self {
+            food,
+            obstacles,
+            nests: match nests.is_empty() {
+                true => None,
+                false => Some(Nests { nests }),
+            },
+        })
+    }
+
+    // Every connected blob of nest cells becomes one round nest of the same area.
+    fn find_nests(food: &Food, nest_cells: &mut [bool]) -> Vec<Nest> {
+        let dimensions = food.amount.dimensions;
+        let cell_area = food.amount.lattice.x * food.amount.lattice.y;
+        let mut nests = vec![];
+
+        for start in 0..nest_cells.len() {
+            if !nest_cells[start] {
+                continue;
+            }
+            nest_cells[start] = false;
+
+            let mut stack = vec![start];
+            let mut total = Vec2::ZERO;
+            let mut count = 0usize;
+            while let Some(linear) = stack.pop() {
+                let Some((x, y)) = dimensions.to_grid(linear) else {
+                    continue;
+                };
+                total += food.amount.lattice_idx_to_center(x, y);
+                count += 1;
+
+                let neighbours = [
+                    (x.wrapping_sub(1), y),
+                    (x + 1, y),
+                    (x, y.wrapping_sub(1)),
+                    (x, y + 1),
+                ];
+                for neighbour in neighbours {
+                    if let Some(idx) = dimensions.to_linear(neighbour) {
+                        if nest_cells[idx] {
+                            nest_cells[idx] = false;
+                            stack.push(idx);
+                        }
+                    }
+                }
+            }
+
+            nests.push(Nest {
+                center: total / count as f32,
+                radius: (count as f32 * cell_area / PI).sqrt(),
+                stored: 0.0,
+            });
+        }
+        nests
+    }
+}
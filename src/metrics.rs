@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::ant::{Ant, SignalKind, Traits};
+use crate::field::{Food, Signals};
+
+#[derive(Resource, Default)]
+pub struct FoodDelivered {
+    pub total: f32,
+}
+
+#[derive(Resource, Default)]
+pub struct Casualties {
+    pub total: u64,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct Metrics {
+    pub tick: u64,
+    pub food: f32,
+    pub delivered: f32,
+    pub deaths: u64,
+    pub exploring_ants: usize,
+    pub retrieving_ants: usize,
+    pub mean_speed: f32,
+    pub exploring_mass: f32,
+    pub retrieving_mass: f32,
+    pub alarm_mass: f32,
+    pub mean_max_speed: f32,
+    pub mean_wander_amplitude: f32,
+    pub mean_wander_weight: f32,
+    pub mean_trail_weight: f32,
+}
+
+impl Metrics {
+    const CSV_HEADER: &'static str =
+        "tick,food,delivered,deaths,exploring_ants,retrieving_ants,mean_speed,exploring_mass,retrieving_mass,alarm_mass,\
+         mean_max_speed,mean_wander_amplitude,mean_wander_weight,mean_trail_weight";
+
+    pub fn measure<'a>(
+        tick: u64,
+        food: &Food,
+        delivered: &FoodDelivered,
+        casualties: &Casualties,
+        signals: &Signals,
+        ants: impl Iterator<Item = (&'a Ant, &'a Traits)>,
+    ) -> Self {
+        let mut metrics = Self {
+            tick,
+            food: food.amount.total(),
+            delivered: delivered.total,
+            deaths: casualties.total,
+            exploring_mass: signals.exploring.total_magnitude(),
+            retrieving_mass: signals.retrieving.total_magnitude(),
+            alarm_mass: signals.alarm.total_magnitude(),
+            ..default()
+        };
+
+        let mut total_speed = 0.0;
+        let mut total_traits = [0.0; 4];
+        for (ant, traits) in ants {
+            match ant.state {
+                SignalKind::Exploring => metrics.exploring_ants += 1,
+                SignalKind::Retrieving => metrics.retrieving_ants += 1,
+            }
+            total_speed += ant.speed;
+            total_traits[0] += traits.max_speed;
+            total_traits[1] += traits.wander_amplitude;
+            total_traits[2] += traits.wander_weight;
+            total_traits[3] += traits.trail_weight;
+        }
+        let count = (metrics.exploring_ants + metrics.retrieving_ants) as f32;
+        if count > 0.0 {
+            metrics.mean_speed = total_speed / count;
+            metrics.mean_max_speed = total_traits[0] / count;
+            metrics.mean_wander_amplitude = total_traits[1] / count;
+            metrics.mean_wander_weight = total_traits[2] / count;
+            metrics.mean_trail_weight = total_traits[3] / count;
+        }
+        metrics
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            self.tick,
+            self.food,
+            self.delivered,
+            self.deaths,
+            self.exploring_ants,
+            self.retrieving_ants,
+            self.mean_speed,
+            self.exploring_mass,
+            self.retrieving_mass,
+            self.alarm_mass,
+            self.mean_max_speed,
+            self.mean_wander_amplitude,
+            self.mean_wander_weight,
+            self.mean_trail_weight,
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MetricsFormat {
+    Csv,
+    Ndjson,
+}
+
+impl MetricsFormat {
+    // Anything that isn't obviously JSON gets CSV.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json" | "ndjson" | "jsonl") => Self::Ndjson,
+            _ => Self::Csv,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct MetricsLog {
+    file: File,
+    format: MetricsFormat,
+}
+
+impl MetricsLog {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let format = MetricsFormat::from_path(path);
+        let mut file = File::create(path)?;
+        if format == MetricsFormat::Csv {
+            file.write_all(format!("{}\n", Metrics::CSV_HEADER).as_bytes())?;
+        }
+        Ok(Self { file, format })
+    }
+
+    pub fn write(&mut self, metrics: &Metrics) -> io::Result<()> {
+        let line = match self.format {
+            MetricsFormat::Csv => metrics.to_csv(),
+            MetricsFormat::Ndjson => serde_json::to_string(metrics)?,
+        };
+        self.file.write_all(format!("{line}\n").as_bytes())
+    }
+}
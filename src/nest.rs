@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Nest {
+    pub center: Vec2,
+    pub radius: f32,
+    pub stored: f32,
+}
+
+impl Nest {
+    pub fn contains(&self, pos: Vec2) -> bool {
+        (pos - self.center).length_squared() < self.radius * self.radius
+    }
+}
+
+#[derive(Resource)]
+pub struct Nests {
+    pub nests: Vec<Nest>,
+}
+
+impl Nests {
+    pub fn single(center: Vec2, radius: f32) -> Self {
+        Self {
+            nests: vec![Nest { center, radius, stored: 0.0 }],
+        }
+    }
+
+    pub fn contains(&self, pos: Vec2) -> bool {
+        self.nests.iter().any(|nest| nest.contains(pos))
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt::Debug;
+
+use bevy::prelude::*;
+
+use crate::field::{Signals, Vec2Field};
+use crate::texture::{to_byte, texel_offset};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum OverlayMode {
+    #[default]
+    Off,
+    Heatmap,
+    Arrows,
+    Both,
+}
+
+impl OverlayMode {
+    pub fn next(self) -> Self {
+        match self {
+            OverlayMode::Off => OverlayMode::Heatmap,
+            OverlayMode::Heatmap => OverlayMode::Arrows,
+            OverlayMode::Arrows => OverlayMode::Both,
+            OverlayMode::Both => OverlayMode::Off,
+        }
+    }
+
+    pub fn heatmap(self) -> bool {
+        matches!(self, OverlayMode::Heatmap | OverlayMode::Both)
+    }
+
+    pub fn arrows(self) -> bool {
+        matches!(self, OverlayMode::Arrows | OverlayMode::Both)
+    }
+}
+
+// Maps a pheromone magnitude onto [0, 1], reaching 1 at `saturation`.
+#[derive(Copy, Clone, Debug)]
+pub struct ColourScale {
+    pub saturation: f32,
+    pub log: bool,
+}
+
+impl ColourScale {
+    pub const SATURATIONS: (f32, f32) = (0.1, 10000.0);
+
+    pub fn map(&self, magnitude: f32) -> f32 {
+        let t = match self.log {
+            true => magnitude.ln_1p() / self.saturation.ln_1p(),
+            false => magnitude / self.saturation,
+        };
+        t.clamp(0.0, 1.0)
+    }
+
+    pub fn rescale(&mut self, factor: f32) {
+        self.saturation = (self.saturation * factor).clamp(Self::SATURATIONS.0, Self::SATURATIONS.1);
+    }
+}
+
+impl Default for ColourScale {
+    fn default() -> Self {
+        Self {
+            saturation: 50.0,
+            log: false,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Overlay {
+    pub mode: OverlayMode,
+    pub scale: ColourScale,
+    pub image: Handle<Image>,
+}
+
+fn magnitude<T: Copy + Clone + Debug>(field: &Vec2Field<T>, i: usize) -> f32 {
+    let (x, y) = field.get_values();
+    Vec2::new(x[i], y[i]).length()
+}
+
+// Exploring in red, retrieving in green and alarm in blue, each by magnitude.
+pub fn heatmap_pixels(signals: &Signals, scale: &ColourScale, pixels: &mut [u8]) {
+    let dimensions = signals.exploring.get_dimensions();
+    for i in 0..dimensions.linear_max() {
+        let Some(grid_idx) = dimensions.to_grid(i) else {
+            continue;
+        };
+        let exploring = scale.map(magnitude(&signals.exploring, i));
+        let retrieving = scale.map(magnitude(&signals.retrieving, i));
+        let alarm = scale.map(magnitude(&signals.alarm, i));
+        let offset = texel_offset(dimensions, grid_idx);
+        pixels[offset..offset + 4].copy_from_slice(&[
+            to_byte(exploring),
+            to_byte(retrieving),
+            to_byte(alarm),
+            to_byte(exploring.max(retrieving).max(alarm)),
+        ]);
+    }
+}
+
+// Cell centres and values for every `stride`-th cell in each direction.
+pub fn arrows<T: Copy + Clone + Debug>(field: &Vec2Field<T>, stride: usize) -> Vec<(Vec2, Vec2)> {
+    let dimensions = field.get_dimensions();
+    let (xs, ys) = field.get_values();
+    let stride = stride.max(1);
+    let mut arrows = vec![];
+    for y in (0..dimensions.h()).step_by(stride) {
+        for x in (0..dimensions.w()).step_by(stride) {
+            let Some(i) = dimensions.to_linear((x, y)) else {
+                continue;
+            };
+            arrows.push((field.lattice_idx_to_center(x, y), Vec2::new(xs[i], ys[i])));
+        }
+    }
+    arrows
+}
@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+use image::{ImageResult, Rgba, RgbaImage};
+
+use crate::batch::{state_color, triangle};
+use crate::field::{Cellular, Food, Obstacles, Signals};
+use crate::hazard::Hazard;
+use crate::nest::Nests;
+use crate::overlay::ColourScale;
+use crate::replay::AntFrame;
+
+// Everything a frame shows. Signals are left out unless asked for, as they
+// would hide the food underneath.
+pub struct Scene<'a> {
+    pub food: &'a Food,
+    pub max_food: f32,
+    pub obstacles: &'a Obstacles,
+    pub nests: &'a Nests,
+    pub hazards: &'a [Hazard],
+    pub signals: Option<(&'a Signals, ColourScale)>,
+    pub ants: &'a [AntFrame],
+}
+
+// Draws the world on the CPU, one pixel per world unit with the origin in the
+// middle, so frames can be produced without a window or a GPU.
+pub struct Canvas {
+    pub image: RgbaImage,
+}
+
+impl Canvas {
+    pub fn new(size: Vec2, background: Color) -> Self {
+        Self {
+            image: RgbaImage::from_pixel(size.x as u32, size.y as u32, Rgba(to_rgba8(background))),
+        }
+    }
+
+    // Back to front, in the same order the window stacks its sprites: nests,
+    // hazards, walls, food, signals, then ants on top.
+    pub fn render(size: Vec2, background: Color, scene: &Scene) -> Self {
+        let mut canvas = Self::new(size, background);
+        for nest in &scene.nests.nests {
+            canvas.draw_circle(nest.center, nest.radius, Color::PURPLE);
+        }
+        for hazard in scene.hazards {
+            canvas.draw_circle(Vec2::from_array(hazard.center), hazard.radius, Color::rgba(0.8, 0.0, 0.0, 0.4));
+        }
+        canvas.draw_field(|pos| match scene.obstacles.is_blocked(pos) {
+            true => Some(Color::GRAY),
+            false => None,
+        });
+        canvas.draw_field(|pos| {
+            let amount = scene.food.amount.get_cell_value(pos);
+            match amount > 0.0 {
+                true => Some(Color::rgba(0.7, 0.7, 0.0, (amount / scene.max_food).min(1.0))),
+                false => None,
+            }
+        });
+        if let Some((signals, scale)) = &scene.signals {
+            canvas.draw_field(|pos| {
+                let [exploring, retrieving, alarm] = [
+                    signals.exploring.sample(pos),
+                    signals.retrieving.sample(pos),
+                    signals.alarm.sample(pos),
+                ]
+                .map(|value| scale.map(value.length()));
+                let alpha = exploring.max(retrieving).max(alarm);
+                match alpha > 0.0 {
+                    true => Some(Color::rgba(exploring, retrieving, alarm, alpha)),
+                    false => None,
+                }
+            });
+        }
+        canvas.draw_ants(scene.ants);
+        canvas
+    }
+
+    fn size(&self) -> Vec2 {
+        Vec2::new(self.image.width() as f32, self.image.height() as f32)
+    }
+
+    // The image is stored top row first but world y points up.
+    fn to_pixel(&self, pos: Vec2) -> Vec2 {
+        Vec2::new(pos.x, -pos.y) + 0.5 * self.size()
+    }
+
+    fn to_world(&self, x: u32, y: u32) -> Vec2 {
+        let pixel = Vec2::new(x as f32, y as f32) + 0.5 - 0.5 * self.size();
+        Vec2::new(pixel.x, -pixel.y)
+    }
+
+    fn blend(&mut self, x: u32, y: u32, color: Color) {
+        let [r, g, b, a] = color.as_rgba_f32();
+        let pixel = self.image.get_pixel_mut(x, y);
+        for (channel, value) in pixel.0.iter_mut().zip([r, g, b]) {
+            *channel = (a * 255.0 * value + (1.0 - a) * *channel as f32).round() as u8;
+        }
+        pixel.0[3] = 255;
+    }
+
+    // Colours every pixel by whatever `shade` makes of the world point under it.
+    pub fn draw_field(&mut self, shade: impl Fn(Vec2) -> Option<Color>) {
+        for y in 0..self.image.height() {
+            for x in 0..self.image.width() {
+                if let Some(color) = shade(self.to_world(x, y)) {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    // Fills the pixels whose centres fall inside the polygon given by `inside`,
+    // searching only the bounding box of `corners`.
+    fn fill(&mut self, corners: &[Vec2], color: Color, inside: impl Fn(Vec2) -> bool) {
+        let pixels: Vec<Vec2> = corners.iter().map(|&corner| self.to_pixel(corner)).collect();
+        let min = pixels.iter().fold(Vec2::INFINITY, |a, &b| a.min(b)).max(Vec2::ZERO);
+        let max = pixels.iter().fold(Vec2::NEG_INFINITY, |a, &b| a.max(b)).min(self.size());
+        for y in min.y.floor() as u32..max.y.ceil() as u32 {
+            for x in min.x.floor() as u32..max.x.ceil() as u32 {
+                if inside(self.to_world(x, y)) {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    pub fn draw_circle(&mut self, center: Vec2, radius: f32, color: Color) {
+        let corners = [center - Vec2::splat(radius), center + Vec2::splat(radius)];
+        self.fill(&corners, color, |pos| pos.distance_squared(center) <= radius * radius);
+    }
+
+    pub fn draw_triangle(&mut self, corners: [Vec2; 3], color: Color) {
+        let [a, b, c] = corners;
+        let edge = |from: Vec2, to: Vec2, pos: Vec2| (to - from).perp_dot(pos - from);
+        self.fill(&corners, color, |pos| {
+            let sides = [edge(a, b, pos), edge(b, c, pos), edge(c, a, pos)];
+            sides.iter().all(|&side| side >= 0.0) || sides.iter().all(|&side| side <= 0.0)
+        });
+    }
+
+    pub fn draw_ants(&mut self, ants: &[AntFrame]) {
+        for ant in ants {
+            self.draw_triangle(triangle(ant), state_color(ant.state));
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> ImageResult<()> {
+        self.image.save(path)
+    }
+}
+
+fn to_rgba8(color: Color) -> [u8; 4] {
+    color.as_rgba_f32().map(|value| (255.0 * value.clamp(0.0, 1.0)).round() as u8)
+}
@@ -0,0 +1,210 @@
+// Runs fixed, seeded scenarios headless and compares the end state against
+// golden files in tests/golden: a handful of field checksums within a relative
+// tolerance, and a software-rendered frame within a per-pixel tolerance.
+//
+// Run with UPDATE_GOLDEN=1 to write the golden files from the current build,
+// then check the images by eye before committing them.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::Outputs;
+use crate::config::{CasteRatios, Config};
+use crate::field::{Food, Obstacles, Signals};
+use crate::hazard::{Hazard, Predators};
+use crate::metrics::{Casualties, FoodDelivered};
+use crate::nest::Nests;
+
+const RELATIVE_TOLERANCE: f64 = 1e-3;
+// Channel difference a pixel may show before it counts as changed, and the
+// share of pixels allowed to change.
+const PIXEL_TOLERANCE: u8 = 32;
+const CHANGED_PIXELS: f64 = 0.005;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Checksums {
+    ants: usize,
+    deaths: u64,
+    food: f64,
+    delivered: f64,
+    stored: f64,
+    exploring: f64,
+    retrieving: f64,
+    alarm: f64,
+    // Sum of ant positions, so a drift in where ants go shows up even when
+    // the totals above stay put.
+    positions: (f64, f64),
+}
+
+impl Checksums {
+    fn compare(&self, golden: &Self) -> Vec<String> {
+        let mut failures = vec![];
+        if self.ants != golden.ants {
+            failures.push(format!("ants: {} != {}", self.ants, golden.ants));
+        }
+        if self.deaths != golden.deaths {
+            failures.push(format!("deaths: {} != {}", self.deaths, golden.deaths));
+        }
+        for (name, actual, expected) in [
+            ("food", self.food, golden.food),
+            ("delivered", self.delivered, golden.delivered),
+            ("stored", self.stored, golden.stored),
+            ("exploring", self.exploring, golden.exploring),
+            ("retrieving", self.retrieving, golden.retrieving),
+            ("alarm", self.alarm, golden.alarm),
+            ("positions.x", self.positions.0, golden.positions.0),
+            ("positions.y", self.positions.1, golden.positions.1),
+        ] {
+            let scale = expected.abs().max(1.0);
+            if (actual - expected).abs() > RELATIVE_TOLERANCE * scale {
+                failures.push(format!("{name}: {actual} != {expected}"));
+            }
+        }
+        failures
+    }
+}
+
+struct Outcome {
+    checksums: Checksums,
+    image: RgbaImage,
+}
+
+fn run(config: Config, ticks: u64) -> Outcome {
+    let mut app = crate::headless(config, ticks, Outputs::default());
+    let frames = crate::ant_frames(&mut app.world);
+
+    let world = &app.world;
+    let signals = world.resource::<Signals>();
+    let positions = frames.iter().fold((0.0, 0.0), |(x, y), ant| {
+        (x + ant.position.x as f64, y + ant.position.y as f64)
+    });
+    let checksums = Checksums {
+        ants: frames.len(),
+        deaths: world.resource::<Casualties>().total,
+        food: world.resource::<Food>().amount.total() as f64,
+        delivered: world.resource::<FoodDelivered>().total as f64,
+        stored: world.resource::<Nests>().nests.iter().map(|nest| nest.stored as f64).sum(),
+        exploring: signals.exploring.total_magnitude() as f64,
+        retrieving: signals.retrieving.total_magnitude() as f64,
+        alarm: signals.alarm.total_magnitude() as f64,
+        positions,
+    };
+    let canvas = crate::raster_frame(
+        world.resource::<Food>(),
+        world.resource::<Obstacles>(),
+        world.resource::<Nests>(),
+        world.resource::<Config>(),
+        // pheromone saturates and would hide the ants, and its mass is
+        // already in the checksums
+        None,
+        &frames,
+    );
+    Outcome {
+        checksums,
+        image: canvas.image,
+    }
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn compare_images(actual: &RgbaImage, golden: &RgbaImage) -> Option<String> {
+    if actual.dimensions() != golden.dimensions() {
+        return Some(format!("image is {:?}, golden is {:?}", actual.dimensions(), golden.dimensions()));
+    }
+    let changed = actual
+        .pixels()
+        .zip(golden.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(&x, y)| x.abs_diff(y) > PIXEL_TOLERANCE))
+        .count();
+    let share = changed as f64 / (actual.width() * actual.height()) as f64;
+    match share > CHANGED_PIXELS {
+        true => Some(format!("{:.2}% of pixels changed", 100.0 * share)),
+        false => None,
+    }
+}
+
+fn check(name: &str, config: Config, ticks: u64) {
+    let outcome = run(config, ticks);
+    let dir = golden_dir();
+    let (checksum_path, image_path) = (dir.join(format!("{name}.ron")), dir.join(format!("{name}.png")));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(&dir).unwrap();
+        let text = ron::ser::to_string_pretty(&outcome.checksums, ron::ser::PrettyConfig::default()).unwrap();
+        fs::write(&checksum_path, text).unwrap();
+        outcome.image.save(&image_path).unwrap();
+        return;
+    }
+
+    let missing = |path: &PathBuf| format!("No golden file at {path:?}, run with UPDATE_GOLDEN=1 to create it");
+    let golden: Checksums =
+        ron::from_str(&fs::read_to_string(&checksum_path).unwrap_or_else(|_| panic!("{}", missing(&checksum_path))))
+            .unwrap_or_else(|err| panic!("Could not parse {checksum_path:?}: {err}"));
+    let golden_image = image::open(&image_path)
+        .unwrap_or_else(|_| panic!("{}", missing(&image_path)))
+        .to_rgba8();
+
+    let mut failures = outcome.checksums.compare(&golden);
+    failures.extend(compare_images(&outcome.image, &golden_image));
+    if !failures.is_empty() {
+        // keep what we got next to the build so it can be looked at
+        let actual = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("regression");
+        let _ = fs::create_dir_all(&actual);
+        let _ = outcome.image.save(actual.join(format!("{name}.png")));
+        panic!("Scenario {name} no longer matches its golden files:\n{}", failures.join("\n"));
+    }
+}
+
+fn base_config() -> Config {
+    Config {
+        seed: Some(7),
+        ants: 200,
+        ..default()
+    }
+}
+
+#[test]
+fn foraging() {
+    check("foraging", base_config(), 1200);
+}
+
+#[test]
+fn castes_under_threat() {
+    let config = Config {
+        castes: CasteRatios {
+            worker: 0.6,
+            scout: 0.2,
+            soldier: 0.2,
+        },
+        // right on the way between the food and the nest
+        hazards: vec![Hazard {
+            center: [350.0, 250.0],
+            radius: 60.0,
+            lethality: 2.0,
+        }],
+        predators: Predators {
+            count: 3,
+            ..default()
+        },
+        ..base_config()
+    };
+    check("castes_under_threat", config, 1200);
+}
+
+#[test]
+fn colony_growth() {
+    let config = Config {
+        trait_spread: 0.2,
+        birth_cost: Some(1.0),
+        lifespan: Some(15.0),
+        ..base_config()
+    };
+    check("colony_growth", config, 1200);
+}
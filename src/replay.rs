@@ -0,0 +1,405 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::ant::{Ant, Caste, SignalKind};
+
+// File layout: MAGIC, VERSION, then one frame per tick. A frame is
+//   varint tick, varint ant count, u8 keyframe flag,
+//   then per ant: zigzag varint dx, zigzag varint dy, u8 heading, u8 state,
+//   where the state byte carries the caste in its high nibble.
+// Positions are stored in 1/POSITION_SCALE pixel steps as deltas against the
+// same slot in the previous frame, or against zero on keyframes.
+const MAGIC: &[u8; 4] = b"ANTR";
+const VERSION: u8 = 1;
+const POSITION_SCALE: f32 = 8.0;
+const KEYFRAME_INTERVAL: u64 = 256;
+// Two one-byte varints, heading and state.
+const MIN_ANT_BYTES: usize = 4;
+
+#[derive(Copy, Clone, Debug)]
+pub struct AntFrame {
+    pub position: Vec2,
+    pub heading: f32,
+    pub state: SignalKind,
+    pub caste: Caste,
+}
+
+impl AntFrame {
+    pub fn capture(ant: &Ant, caste: Caste) -> Self {
+        Self {
+            position: ant.position,
+            heading: ant.velocity.y.atan2(ant.velocity.x),
+            state: ant.state,
+            caste,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+struct Quantized {
+    x: i32,
+    y: i32,
+    heading: u8,
+    state: u8,
+}
+
+impl Quantized {
+    fn from_frame(frame: &AntFrame) -> Self {
+        let turns = frame.heading / std::f32::consts::TAU;
+        Self {
+            x: (frame.position.x * POSITION_SCALE).round() as i32,
+            y: (frame.position.y * POSITION_SCALE).round() as i32,
+            heading: (turns * 256.0).round().rem_euclid(256.0) as u8,
+            state: frame.state as u8 | (frame.caste as u8) << 4,
+        }
+    }
+
+    fn to_frame(self) -> AntFrame {
+        AntFrame {
+            position: Vec2::new(self.x as f32, self.y as f32) / POSITION_SCALE,
+            heading: self.heading as f32 / 256.0 * std::f32::consts::TAU,
+            state: match self.state & 0x0f {
+                0 => SignalKind::Exploring,
+                _ => SignalKind::Retrieving,
+            },
+            caste: Caste::from_u8(self.state >> 4),
+        }
+    }
+}
+
+pub struct Recorder {
+    file: File,
+    previous: Vec<Quantized>,
+    frames: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+        file.write_all(MAGIC)?;
+        file.write_all(&[VERSION])?;
+        Ok(Self {
+            file,
+            previous: vec![],
+            frames: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, tick: u64, ants: &[AntFrame]) -> io::Result<()> {
+        let keyframe = self.frames % KEYFRAME_INTERVAL == 0;
+        let mut buf = vec![];
+        write_varint(&mut buf, tick);
+        write_varint(&mut buf, ants.len() as u64);
+        buf.push(keyframe as u8);
+
+        let current: Vec<Quantized> = ants.iter().map(Quantized::from_frame).collect();
+        for (i, ant) in current.iter().enumerate() {
+            let base = match keyframe {
+                true => Quantized::default(),
+                false => self.previous.get(i).copied().unwrap_or_default(),
+            };
+            write_varint(&mut buf, zigzag(ant.x.wrapping_sub(base.x)));
+            write_varint(&mut buf, zigzag(ant.y.wrapping_sub(base.y)));
+            buf.push(ant.heading);
+            buf.push(ant.state);
+        }
+
+        // One write per frame keeps the file usable if the app is killed.
+        self.file.write_all(&buf)?;
+        self.previous = current;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+struct FrameIndex {
+    tick: u64,
+    offset: usize,
+    keyframe: bool,
+}
+
+pub struct Player {
+    data: Vec<u8>,
+    index: Vec<FrameIndex>,
+    current: Option<(usize, Vec<Quantized>)>,
+}
+
+impl Player {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        if data.len() < MAGIC.len() + 1 || &data[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("Not an ant recording."));
+        }
+        if data[MAGIC.len()] != VERSION {
+            return Err(invalid_data(format!(
+                "Recording version {} is not supported, expected {VERSION}.",
+                data[MAGIC.len()]
+            )));
+        }
+
+        let mut player = Self {
+            data,
+            index: vec![],
+            current: None,
+        };
+        let mut offset = MAGIC.len() + 1;
+        let mut previous = vec![];
+        while offset < player.data.len() {
+            // A run that was killed mid-write leaves a partial frame at the end.
+            let Ok((tick, keyframe, ants, next)) = player.decode(offset, &previous) else {
+                warn!("Ignoring truncated frame at the end of {path:?}");
+                break;
+            };
+            player.index.push(FrameIndex {
+                tick,
+                offset,
+                keyframe,
+            });
+            previous = ants;
+            offset = next;
+        }
+        Ok(player)
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn tick(&self, frame: usize) -> Option<u64> {
+        self.index.get(frame).map(|entry| entry.tick)
+    }
+
+    pub fn frame(&mut self, frame: usize) -> Option<Vec<AntFrame>> {
+        if frame >= self.index.len() {
+            return None;
+        }
+
+        // Step forward from wherever we are if that is cheaper than going
+        // back to the last keyframe.
+        let keyframe = (0..=frame).rev().find(|&i| self.index[i].keyframe).unwrap_or(0);
+        let (mut at, mut ants) = match self.current.take() {
+            Some((at, ants)) if at <= frame && at >= keyframe => (at, ants),
+            _ => {
+                let (_, _, ants, _) = self.decode(self.index[keyframe].offset, &[]).ok()?;
+                (keyframe, ants)
+            }
+        };
+        while at < frame {
+            at += 1;
+            let (_, _, next, _) = self.decode(self.index[at].offset, &ants).ok()?;
+            ants = next;
+        }
+
+        let frames = ants.iter().map(|ant| ant.to_frame()).collect();
+        self.current = Some((at, ants));
+        Some(frames)
+    }
+
+    fn decode(&self, offset: usize, previous: &[Quantized]) -> io::Result<(u64, bool, Vec<Quantized>, usize)> {
+        let mut cursor = offset;
+        let tick = read_varint(&self.data, &mut cursor)?;
+        let count = read_varint(&self.data, &mut cursor)? as usize;
+        let keyframe = read_byte(&self.data, &mut cursor)? != 0;
+
+        // the count is only as trustworthy as the file, so don't let it
+        // reserve more than the bytes left could ever hold
+        let remaining = self.data.len().saturating_sub(cursor);
+        let mut ants = Vec::with_capacity(count.min(remaining / MIN_ANT_BYTES));
+        for i in 0..count {
+            let base = match keyframe {
+                true => Quantized::default(),
+                false => previous.get(i).copied().unwrap_or_default(),
+            };
+            let dx = unzigzag(read_varint(&self.data, &mut cursor)?);
+            let dy = unzigzag(read_varint(&self.data, &mut cursor)?);
+            ants.push(Quantized {
+                x: base.x.wrapping_add(dx),
+                y: base.y.wrapping_add(dy),
+                heading: read_byte(&self.data, &mut cursor)?,
+                state: read_byte(&self.data, &mut cursor)?,
+            });
+        }
+        Ok((tick, keyframe, ants, cursor))
+    }
+}
+
+fn zigzag(value: i32) -> u64 {
+    ((value << 1) ^ (value >> 31)) as u32 as u64
+}
+
+fn unzigzag(value: u64) -> i32 {
+    let value = value as u32;
+    ((value >> 1) as i32) ^ -((value & 1) as i32)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn read_varint(data: &[u8], cursor: &mut usize) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(data, cursor)?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("Varint is too long."))
+}
+
+fn read_byte(data: &[u8], cursor: &mut usize) -> io::Result<u8> {
+    let Some(&byte) = data.get(*cursor) else {
+        return Err(invalid_data("Recording ends mid-frame."));
+    };
+    *cursor += 1;
+    Ok(byte)
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ants-{}-{name}.antr", std::process::id()))
+    }
+
+    // Positions on the 1/POSITION_SCALE grid and headings on the 1/256 turn
+    // grid, so they come back exactly. Every fifth ant is retrieving and the
+    // castes take turns.
+    fn ants_at(tick: u64, count: usize) -> Vec<AntFrame> {
+        (0..count)
+            .map(|i| AntFrame {
+                position: Vec2::new(10.0 * i as f32 + 0.5 * tick as f32, -3.25 * i as f32 + 0.125 * tick as f32),
+                heading: ((tick as usize + i) % 256) as f32 / 256.0 * TAU,
+                state: match i % 5 {
+                    0 => SignalKind::Retrieving,
+                    _ => SignalKind::Exploring,
+                },
+                caste: Caste::from_u8((i % 3) as u8),
+            })
+            .collect()
+    }
+
+    // The colony grows partway through so slots appear between keyframes.
+    fn count_at(tick: u64) -> usize {
+        if tick < 100 { 20 } else { 25 }
+    }
+
+    fn assert_frames_eq(actual: &[AntFrame], expected: &[AntFrame]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(a.position.distance(e.position) < 1e-4, "{a:?} != {e:?}");
+            assert!((a.heading - e.heading).abs() < 1e-4, "{a:?} != {e:?}");
+            assert_eq!((a.state, a.caste), (e.state, e.caste));
+        }
+    }
+
+    #[test]
+    fn zigzag_round_trips() {
+        assert_eq!([0, -1, 1, -2].map(zigzag), [0, 1, 2, 3]);
+        for value in [0, 1, -1, 63, -64, 12345, -98765, i32::MAX, i32::MIN] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+
+    #[test]
+    fn varint_round_trips() {
+        let values = [0, 1, 127, 128, 300, 16383, 16384, u32::MAX as u64, u64::MAX];
+        let mut buf = vec![];
+        for value in values {
+            write_varint(&mut buf, value);
+        }
+        let mut cursor = 0;
+        for value in values {
+            assert_eq!(read_varint(&buf, &mut cursor).unwrap(), value);
+        }
+        assert_eq!(cursor, buf.len());
+
+        let (mut short, mut long) = (vec![], vec![]);
+        write_varint(&mut short, 127);
+        write_varint(&mut long, 128);
+        assert_eq!((short.len(), long.len()), (1, 2));
+        assert!(read_varint(&long[..1], &mut 0).is_err());
+    }
+
+    #[test]
+    fn recording_round_trips_across_keyframes() {
+        let path = temp_path("round-trip");
+        let ticks = 300;
+        let mut recorder = Recorder::create(&path).unwrap();
+        for tick in 0..ticks {
+            recorder.write_frame(tick, &ants_at(tick, count_at(tick))).unwrap();
+        }
+        drop(recorder);
+
+        let mut player = Player::open(&path).unwrap();
+        assert_eq!(player.len(), ticks as usize);
+        for frame in 0..ticks {
+            assert_eq!(player.tick(frame as usize), Some(frame));
+            assert_frames_eq(&player.frame(frame as usize).unwrap(), &ants_at(frame, count_at(frame)));
+        }
+        assert!(player.frame(ticks as usize).is_none());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn seeking_backwards_past_a_keyframe() {
+        let path = temp_path("seek");
+        let mut recorder = Recorder::create(&path).unwrap();
+        for tick in 0..300 {
+            recorder.write_frame(tick, &ants_at(tick, count_at(tick))).unwrap();
+        }
+        drop(recorder);
+
+        let mut player = Player::open(&path).unwrap();
+        // 256 is a keyframe, so each of these jumps back over one
+        for frame in [290, 250, 280, 10, 299, 255] {
+            assert_frames_eq(&player.frame(frame).unwrap(), &ants_at(frame as u64, count_at(frame as u64)));
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn truncated_and_corrupt_frames_are_dropped() {
+        let path = temp_path("corrupt");
+        let mut recorder = Recorder::create(&path).unwrap();
+        for tick in 0..3 {
+            recorder.write_frame(tick, &ants_at(tick, 20)).unwrap();
+        }
+        drop(recorder);
+
+        // half a frame, then a frame claiming far more ants than could fit
+        let mut data = fs::read(&path).unwrap();
+        let whole = data.len();
+        let half = data[MAGIC.len() + 1..][..10].to_vec();
+        data.extend_from_slice(&half);
+        fs::write(&path, &data).unwrap();
+        assert_eq!(Player::open(&path).unwrap().len(), 3);
+
+        data.truncate(whole);
+        write_varint(&mut data, 3);
+        write_varint(&mut data, u64::MAX >> 8);
+        data.push(1);
+        fs::write(&path, &data).unwrap();
+        assert_eq!(Player::open(&path).unwrap().len(), 3);
+
+        fs::write(&path, b"ANTS").unwrap();
+        assert!(Player::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy_prng::ChaCha8Rng;
+use bevy_rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ant::{Ant, Caste, SignalKind, Traits};
+use crate::field::{Field, Food, LatticeIndexer, Obstacles, Signals, Vec2Field};
+use crate::hazard::Predator;
+use crate::metrics::{Casualties, FoodDelivered};
+use crate::nest::{Nest, Nests};
+use crate::Inventory;
+
+#[derive(Serialize, Deserialize)]
+pub struct FieldSnapshot {
+    pub dimensions: (usize, usize),
+    pub cells: Vec<f32>,
+}
+
+impl FieldSnapshot {
+    fn new(dimensions: LatticeIndexer, cells: &[f32]) -> Self {
+        Self {
+            dimensions: (dimensions.w(), dimensions.h()),
+            cells: cells.to_vec(),
+        }
+    }
+
+    fn capture<T: Copy + Clone + Debug>(field: &Field<T>) -> Self {
+        Self::new(field.dimensions, field.get_values())
+    }
+
+    fn check(&self, dimensions: LatticeIndexer) -> io::Result<()> {
+        if self.dimensions != (dimensions.w(), dimensions.h()) {
+            return Err(invalid_data(format!(
+                "Snapshot field is {:?} cells but the simulation has {:?}.",
+                self.dimensions,
+                (dimensions.w(), dimensions.h()),
+            )));
+        }
+        Ok(())
+    }
+
+    fn restore<T: Copy + Clone + Debug>(self, field: &mut Field<T>) {
+        field.set_values(self.cells);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Vec2FieldSnapshot {
+    pub x: FieldSnapshot,
+    pub y: FieldSnapshot,
+}
+
+impl Vec2FieldSnapshot {
+    fn capture<T: Copy + Clone + Debug>(field: &Vec2Field<T>) -> Self {
+        let (x, y) = field.get_values();
+        Self {
+            x: FieldSnapshot::new(field.get_dimensions(), x),
+            y: FieldSnapshot::new(field.get_dimensions(), y),
+        }
+    }
+
+    fn check(&self, dimensions: LatticeIndexer) -> io::Result<()> {
+        self.x.check(dimensions)?;
+        self.y.check(dimensions)
+    }
+
+    fn restore<T: Copy + Clone + Debug>(self, field: &mut Vec2Field<T>) {
+        field.set_values(self.x.cells, self.y.cells);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AntSnapshot {
+    pub state: SignalKind,
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+    pub capacity: f32,
+    pub contents: f32,
+    #[serde(default)]
+    pub age: f32,
+    #[serde(default)]
+    pub traits: Option<Traits>,
+    #[serde(default)]
+    pub caste: Caste,
+}
+
+impl AntSnapshot {
+    pub fn capture(ant: &Ant, inventory: &Inventory, traits: &Traits, caste: Caste) -> Self {
+        Self {
+            state: ant.state,
+            position: ant.position.to_array(),
+            velocity: ant.velocity.to_array(),
+            capacity: inventory.capacity,
+            contents: inventory.contents,
+            age: ant.age,
+            traits: Some(*traits),
+            caste,
+        }
+    }
+
+    pub fn restore(&self, default_traits: Traits) -> (Ant, Inventory, Traits, Caste) {
+        let mut ant = Ant::new();
+        ant.state = self.state;
+        ant.position = Vec2::from_array(self.position);
+        ant.velocity = Vec2::from_array(self.velocity);
+        ant.age = self.age;
+
+        let mut inventory = Inventory::new(self.capacity);
+        inventory.contents = self.contents;
+        (ant, inventory, self.traits.unwrap_or(default_traits), self.caste)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PredatorSnapshot {
+    pub position: [f32; 2],
+    pub velocity: [f32; 2],
+}
+
+impl PredatorSnapshot {
+    pub fn capture(predator: &Predator) -> Self {
+        Self {
+            position: predator.position.to_array(),
+            velocity: predator.velocity.to_array(),
+        }
+    }
+
+    pub fn restore(&self) -> Predator {
+        Predator {
+            position: Vec2::from_array(self.position),
+            velocity: Vec2::from_array(self.velocity),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NestSnapshot {
+    pub center: [f32; 2],
+    pub radius: f32,
+    #[serde(default)]
+    pub stored: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub tick: u64,
+    pub rng: GlobalEntropy<ChaCha8Rng>,
+    // Food delivered so far, so metrics carry on from where the save left off.
+    #[serde(default)]
+    pub delivered: f32,
+    #[serde(default)]
+    pub deaths: u64,
+    pub food: FieldSnapshot,
+    pub walls: FieldSnapshot,
+    pub exploring: Vec2FieldSnapshot,
+    pub retrieving: Vec2FieldSnapshot,
+    #[serde(default)]
+    pub alarm: Option<Vec2FieldSnapshot>,
+    pub nests: Vec<NestSnapshot>,
+    pub ants: Vec<AntSnapshot>,
+    // Missing from version 1 files.
+    #[serde(default)]
+    pub predators: Option<Vec<PredatorSnapshot>>,
+}
+
+impl Snapshot {
+    // Bump whenever the layout above changes. Files from OLDEST on still
+    // load, with whatever they lack taking its default; older ones are
+    // rejected.
+    pub const VERSION: u32 = 2;
+    pub const OLDEST: u32 = 1;
+
+    pub fn capture<'a>(
+        tick: u64,
+        rng: &GlobalEntropy<ChaCha8Rng>,
+        (delivered, casualties): (&FoodDelivered, &Casualties),
+        (food, obstacles, signals, nests): (&Food, &Obstacles, &Signals, &Nests),
+        ants: impl Iterator<Item = (&'a Ant, &'a Inventory, &'a Traits, &'a Caste)>,
+        predators: impl Iterator<Item = &'a Predator>,
+    ) -> Self {
+        Self {
+            version: Self::VERSION,
+            tick,
+            rng: rng.clone(),
+            delivered: delivered.total,
+            deaths: casualties.total,
+            food: FieldSnapshot::capture(&food.amount),
+            walls: FieldSnapshot::capture(&obstacles.walls),
+            exploring: Vec2FieldSnapshot::capture(&signals.exploring),
+            retrieving: Vec2FieldSnapshot::capture(&signals.retrieving),
+            alarm: Some(Vec2FieldSnapshot::capture(&signals.alarm)),
+            nests: nests
+                .nests
+                .iter()
+                .map(|nest| NestSnapshot {
+                    center: nest.center.to_array(),
+                    radius: nest.radius,
+                    stored: nest.stored,
+                })
+                .collect(),
+            ants: ants
+                .map(|(ant, inventory, traits, &caste)| AntSnapshot::capture(ant, inventory, traits, caste))
+                .collect(),
+            predators: Some(predators.map(PredatorSnapshot::capture).collect()),
+        }
+    }
+
+    // Writes the fields back in place, or nothing at all if any of them
+    // doesn't fit. Ants and predators are left to the caller since they live
+    // in the ECS rather than in resources.
+    pub fn restore_world(
+        self,
+        food: &mut Food,
+        obstacles: &mut Obstacles,
+        signals: &mut Signals,
+        nests: &mut Nests,
+    ) -> io::Result<(Vec<AntSnapshot>, Option<Vec<PredatorSnapshot>>)> {
+        self.food.check(food.amount.dimensions)?;
+        self.walls.check(obstacles.walls.dimensions)?;
+        self.exploring.check(signals.exploring.get_dimensions())?;
+        self.retrieving.check(signals.retrieving.get_dimensions())?;
+        if let Some(alarm) = &self.alarm {
+            alarm.check(signals.alarm.get_dimensions())?;
+        }
+
+        self.food.restore(&mut food.amount);
+        self.walls.restore(&mut obstacles.walls);
+        self.exploring.restore(&mut signals.exploring);
+        self.retrieving.restore(&mut signals.retrieving);
+        match self.alarm {
+            Some(alarm) => alarm.restore(&mut signals.alarm),
+            None => {
+                // older snapshots predate alarm, so start from calm
+                let cells = signals.alarm.get_values().0.len();
+                signals.alarm.set_values(vec![0.0; cells], vec![0.0; cells]);
+            }
+        }
+        nests.nests = self
+            .nests
+            .iter()
+            .map(|nest| Nest {
+                center: Vec2::from_array(nest.center),
+                radius: nest.radius,
+                stored: nest.stored,
+            })
+            .collect();
+        Ok((self.ants, self.predators))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(invalid_data)?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+
+        // Check the version on its own first so an old file reports that
+        // rather than whichever field happened to change.
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+        let Version { version } = ron::from_str(&text).map_err(invalid_data)?;
+        if !(Self::OLDEST..=Self::VERSION).contains(&version) {
+            return Err(invalid_data(format!(
+                "Snapshot version {version} is not supported, expected {} to {}.",
+                Self::OLDEST,
+                Self::VERSION
+            )));
+        }
+
+        let mut snapshot: Self = ron::from_str(&text).map_err(invalid_data)?;
+        if version == 1 {
+            // version 1 never saved predators, so keep the ones running
+            // rather than clearing them
+            snapshot.predators = None;
+        }
+        Ok(snapshot)
+    }
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
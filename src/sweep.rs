@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+
+use crate::cli::Outputs;
+use crate::config::{Config, Params};
+use crate::field::Food;
+use crate::metrics::FoodDelivered;
+
+// A sweep is the cartesian product of the `grid` axes. Each grid point is then
+// repeated `samples` times with the `random` axes drawn uniformly from their
+// ranges, so a spec with only `random` axes is a plain random search.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct SweepSpec {
+    pub grid: BTreeMap<String, Vec<f32>>,
+    pub random: BTreeMap<String, (f32, f32)>,
+    pub samples: usize,
+}
+
+impl SweepSpec {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+
+    pub fn expand(&self, base: Params, rng: &mut impl Rng) -> Result<Vec<Params>, String> {
+        let mut points = vec![base];
+        for (name, values) in &self.grid {
+            let mut next = vec![];
+            for point in &points {
+                for &value in values {
+                    let mut params = *point;
+                    params.set(name, value)?;
+                    next.push(params);
+                }
+            }
+            points = next;
+        }
+
+        if self.random.is_empty() {
+            return Ok(points);
+        }
+        let mut sampled = vec![];
+        for point in &points {
+            for _ in 0..self.samples.max(1) {
+                let mut params = *point;
+                for (name, &(low, high)) in &self.random {
+                    params.set(name, rng.gen_range(low.min(high)..=high.max(low)))?;
+                }
+                sampled.push(params);
+            }
+        }
+        Ok(sampled)
+    }
+}
+
+pub struct RunSummary {
+    pub params: Params,
+    pub seed: u64,
+    pub ticks: u64,
+    pub delivered: f32,
+    pub food_left: f32,
+    pub seconds: f64,
+}
+
+impl RunSummary {
+    fn header() -> String {
+        let mut columns: Vec<&str> = Params::NAMES.to_vec();
+        columns.extend(["seed", "ticks", "delivered", "delivered_per_tick", "food_left", "seconds"]);
+        columns.join(",")
+    }
+
+    fn to_csv(&self) -> String {
+        let mut columns: Vec<String> = Params::NAMES
+            .iter()
+            .map(|name| self.params.get(name).unwrap_or_default().to_string())
+            .collect();
+        columns.extend([
+            self.seed.to_string(),
+            self.ticks.to_string(),
+            self.delivered.to_string(),
+            (self.delivered / self.ticks.max(1) as f32).to_string(),
+            self.food_left.to_string(),
+            format!("{:.3}", self.seconds),
+        ]);
+        columns.join(",")
+    }
+}
+
+pub fn run_once(mut config: Config, params: Params, seed: u64, ticks: u64) -> RunSummary {
+    config.params = params;
+    config.seed = Some(seed);
+
+    let start = Instant::now();
+    let app = crate::headless(config, ticks, Outputs::default());
+    RunSummary {
+        params,
+        seed,
+        ticks,
+        delivered: app.world.resource::<FoodDelivered>().total,
+        food_left: app.world.resource::<Food>().amount.total(),
+        seconds: start.elapsed().as_secs_f64(),
+    }
+}
+
+// Runs every job on a pool of worker threads and hands each summary back as
+// soon as it is done, in whatever order the workers finish.
+pub fn run_parallel<J, F>(jobs: &[J], threads: usize, run: F, mut done: impl FnMut(usize, RunSummary))
+where
+    J: Sync,
+    F: Fn(&J) -> RunSummary + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let sender = sender.clone();
+            let (next, run) = (&next, &run);
+            scope.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(i) else {
+                    break;
+                };
+                if sender.send((i, run(job))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (i, summary) in receiver {
+            done(i, summary);
+        }
+    });
+}
+
+pub fn sweep(
+    config: Config,
+    spec: &SweepSpec,
+    seeds: u64,
+    ticks: u64,
+    threads: usize,
+    out: &Path,
+) -> io::Result<()> {
+    let base_seed = config.seed.unwrap_or(0);
+    let mut rng = ChaCha8Rng::seed_from_u64(base_seed);
+    let points = spec
+        .expand(config.params, &mut rng)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let jobs: Vec<(Params, u64)> = points
+        .iter()
+        .flat_map(|&params| (0..seeds).map(move |k| (params, base_seed + k)))
+        .collect();
+
+    let mut file = File::create(out)?;
+    file.write_all(format!("{}\n", RunSummary::header()).as_bytes())?;
+
+    let total = jobs.len();
+    let mut finished = 0;
+    let mut result = Ok(());
+    run_parallel(
+        &jobs,
+        threads,
+        |&(params, seed)| run_once(config.clone(), params, seed, ticks),
+        |_, summary| {
+            finished += 1;
+            println!("[{finished}/{total}] seed {} delivered {}", summary.seed, summary.delivered);
+            if result.is_ok() {
+                result = file.write_all(format!("{}\n", summary.to_csv()).as_bytes());
+            }
+        },
+    );
+    result
+}
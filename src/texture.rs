@@ -0,0 +1,60 @@
+use std::fmt::Debug;
+
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+
+use crate::field::{Field, LatticeIndexer};
+
+// Which scalar field a texture shows.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Component)]
+pub enum FieldLayer {
+    Food,
+    Walls,
+    Footfall,
+}
+
+// An RGBA texture with one texel per lattice cell, sampled without smoothing
+// so cell boundaries stay crisp when zoomed in.
+pub fn field_image(dimensions: LatticeIndexer) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: dimensions.w() as u32,
+            height: dimensions.h() as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
+
+// Byte offset of a cell's texel. Images are stored top row first but the
+// lattice starts at the bottom.
+pub fn texel_offset(dimensions: LatticeIndexer, (x, y): (usize, usize)) -> usize {
+    4 * ((dimensions.h() - 1 - y) * dimensions.w() + x)
+}
+
+pub fn to_byte(t: f32) -> u8 {
+    (255.0 * t.clamp(0.0, 1.0)).round() as u8
+}
+
+// Paints every cell in `color`, with its opacity scaled by `opacity(value)`.
+pub fn field_pixels<T: Copy + Clone + Debug>(
+    field: &Field<T>,
+    color: Color,
+    opacity: impl Fn(f32) -> f32,
+    pixels: &mut [u8],
+) {
+    let [r, g, b, a] = color.as_rgba_f32();
+    let (r, g, b) = (to_byte(r), to_byte(g), to_byte(b));
+    for (i, &value) in field.get_values().iter().enumerate() {
+        let Some(grid_idx) = field.dimensions.to_grid(i) else {
+            continue;
+        };
+        let offset = texel_offset(field.dimensions, grid_idx);
+        pixels[offset..offset + 4].copy_from_slice(&[r, g, b, to_byte(a * opacity(value))]);
+    }
+}
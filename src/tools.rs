@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Food,
+    Walls,
+    Nests,
+    Ants,
+    Pheromone,
+    Inspect,
+}
+
+impl Tool {
+    pub const KEYS: [(KeyCode, Tool); 6] = [
+        (KeyCode::Key1, Tool::Food),
+        (KeyCode::Key2, Tool::Walls),
+        (KeyCode::Key3, Tool::Nests),
+        (KeyCode::Key4, Tool::Ants),
+        (KeyCode::Key5, Tool::Pheromone),
+        (KeyCode::Key6, Tool::Inspect),
+    ];
+}
+
+// Left mouse paints with the current tool, shift + left mouse erases.
+#[derive(Resource, Debug)]
+pub struct Toolbox {
+    pub tool: Tool,
+    pub radius: f32,
+    // Cursor position on the previous frame of a stroke, for its direction.
+    pub last: Option<Vec2>,
+    // Nest being dragged around.
+    pub grabbed: Option<usize>,
+    pub scenery_dirty: bool,
+}
+
+impl Toolbox {
+    pub const RADII: (f32, f32) = (5.0, 400.0);
+
+    pub fn resize(&mut self, factor: f32) {
+        self.radius = (self.radius * factor).clamp(Self::RADII.0, Self::RADII.1);
+    }
+}
+
+impl Default for Toolbox {
+    fn default() -> Self {
+        Self {
+            tool: Tool::Food,
+            radius: 25.0,
+            last: None,
+            grabbed: None,
+            scenery_dirty: false,
+        }
+    }
+}
+
+pub fn erasing(keys: &Input<KeyCode>) -> bool {
+    keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+}
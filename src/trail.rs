@@ -0,0 +1,69 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::field::Field;
+
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailMode {
+    #[default]
+    Off,
+    // The last `length` ticks of every ant's path, fading with age.
+    Polylines,
+    // Where ants have walked, accumulated over the whole run.
+    Footfall,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+#[serde(default)]
+pub struct Trails {
+    pub mode: TrailMode,
+    pub length: usize,
+    // Fraction of footfall that fades per second.
+    pub decay: f32,
+}
+
+impl Default for Trails {
+    fn default() -> Self {
+        Self {
+            mode: TrailMode::Off,
+            length: 30,
+            decay: 0.1,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Trail {
+    pub points: VecDeque<Vec2>,
+}
+
+impl Trail {
+    pub fn push(&mut self, position: Vec2, length: usize) {
+        self.points.push_back(position);
+        while self.points.len() > length {
+            self.points.pop_front();
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum Trace {
+    Footfall
+}
+
+#[derive(Resource)]
+pub struct Footfall {
+    pub steps: Field<Trace>,
+}
+
+impl Footfall {
+    pub fn new(lattice: Vec2, size: Vec2) -> Self {
+        Self {
+            steps: Field::new(Trace::Footfall, lattice, size),
+        }
+    }
+}